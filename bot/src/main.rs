@@ -1,12 +1,13 @@
 extern crate db;
 
 use chrono::NaiveDate;
-use db::{Cinema, MovieShort, DB};
+use db::{clock::MOSCOW, repository::ScheduleRepository, Badge, Cinema, MovieShort, DB};
 use lazy_static::lazy_static;
 use log::{error, info};
-use std::{convert::TryFrom, env, error::Error, io, sync::Arc};
+use serde::{Deserialize, Serialize};
+use std::{env, error::Error, io, sync::Arc};
 use teloxide::{
-    dispatching::{dialogue, dialogue::InMemStorage},
+    dispatching::{dialogue, dialogue::ErasedStorage},
     payloads::SendMessageSetters,
     prelude::*,
     types::{InlineKeyboardButton, InlineKeyboardMarkup, Me, MessageId},
@@ -15,19 +16,36 @@ use teloxide::{
 
 mod tg;
 
+use tg::callback::*;
 use tg::callback_handler::*;
 use tg::callbackdata::*;
+use tg::inline::*;
 use tg::keyboard::*;
-use tg::tools::*;
+use tg::storage::*;
 
 lazy_static! {
     static ref DB_ITEMS_PER_PAGE: i64 = env::var("MOSKINO_BOT_ITEMS_PER_PAGE")
         .expect("$MOSKINO_BOT_ITEMS_PER_PAGE is not set")
         .parse()
         .expect("MOSKINO_BOT_ITEMS_PER_PAGE is wrong");
+    // where tickets for a session are bought, {id} - the moskino session id, {date} - YYYY-MM-DD
+    static ref TICKET_URL: String = ticket_url_template(env::var("MOSKINO_BOT_TICKET_URL").ok());
+    // where the dialogues (open menus) are kept: postgres or memory
+    static ref DIALOGUE_STORAGE: String = env::var("MOSKINO_BOT_DIALOGUE_STORAGE").unwrap_or("postgres".to_string());
+    // a dialogue not updated for so long is dropped
+    static ref DIALOGUE_TTL_HOURS: i64 = match env::var("MOSKINO_BOT_DIALOGUE_TTL_HOURS").map(|hours| hours.parse::<i64>()) {
+        Ok(Ok(hours)) if hours > 0 => hours,
+        Err(_) => DEFAULT_DIALOGUE_TTL_HOURS,
+        Ok(_) => {
+            log::warn!("MOSKINO_BOT_DIALOGUE_TTL_HOURS is wrong, {} is used", DEFAULT_DIALOGUE_TTL_HOURS);
+            DEFAULT_DIALOGUE_TTL_HOURS
+        }
+    };
 }
 
-pub type MyDialogue = Dialogue<State, InMemStorage<State>>;
+const DEFAULT_DIALOGUE_TTL_HOURS: i64 = 48;
+
+pub type MyDialogue = Dialogue<State, ErasedStorage<State>>;
 pub type Errr = Box<dyn Error + Send + Sync>;
 pub type Res<T> = Result<T, Errr>;
 
@@ -35,17 +53,22 @@ pub type Res<T> = Result<T, Errr>;
 #[command(rename_rule = "lowercase")]
 pub enum Command {
     Start,
+    // /search <title>
+    Search(String),
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub enum State {
     #[default]
     DayOption,
+    // the badge is the selected filter of the sessions (e.g. only with subtitles)
     StartOption {
         date: NaiveDate,
+        badge: Option<Badge>,
     },
     Cinemas {
         date: NaiveDate,
+        badge: Option<Badge>,
     },
     FromCinema {
         data: CallbackDataCinema,
@@ -67,24 +90,38 @@ async fn main() -> Res<()> {
     let tg_token = env::var("TELOXIDE_TOKEN").expect("$TELOXIDE_TOKEN is not set");
 
     let db = DB::pool(&db_url, db_max_conn).await?;
-    let db = Arc::new(db);
     info!("DB: connected");
 
     sqlx::migrate!("../db/migrations").run(&db.conn).await?;
 
+    let db = Arc::new(db);
+
+    let storage = dialogue_storage(&DIALOGUE_STORAGE, db.clone(), chrono::Duration::hours(*DIALOGUE_TTL_HOURS))?;
+    info!("Dialogues: kept in {}", *DIALOGUE_STORAGE);
+
+    // handlers see the storage through ScheduleRepository only
+    let db: Arc<dyn ScheduleRepository> = db;
+
+    info!("Tickets: {}", *TICKET_URL);
+
     let bot = Bot::new(tg_token);
     info!("TG: token accepted");
 
-    let handler = dialogue::enter::<Update, InMemStorage<State>, State, _>()
-        .branch(Update::filter_message().endpoint(message_handler))
+    let handler = dptree::entry()
+        // inline queries come from any chat, so they have no dialogue
+        .branch(Update::filter_inline_query().endpoint(inline_query_handler))
         .branch(
-            Update::filter_callback_query()
-                // there is should be sub branch in case to split handlers by statements
-                .endpoint(callback_handler),
+            dialogue::enter::<Update, ErasedStorage<State>, State, _>()
+                .branch(Update::filter_message().endpoint(message_handler))
+                .branch(
+                    Update::filter_callback_query()
+                        // there is should be sub branch in case to split handlers by statements
+                        .endpoint(callback_handler),
+                ),
         );
 
     Dispatcher::builder(bot, handler)
-        .dependencies(dptree::deps![InMemStorage::<State>::new(), db])
+        .dependencies(dptree::deps![storage, db])
         .enable_ctrlc_handler()
         .build()
         .dispatch()
@@ -93,11 +130,11 @@ async fn main() -> Res<()> {
     Ok(())
 }
 
-pub async fn message_handler(bot: Bot, dialogue: MyDialogue, msg: Message, me: Me, db: Arc<DB>) -> Res<()> {
+pub async fn message_handler(bot: Bot, dialogue: MyDialogue, msg: Message, me: Me, db: Arc<dyn ScheduleRepository>) -> Res<()> {
     if let Some(text) = msg.text() {
         match BotCommands::parse(text, me.username()) {
             Ok(Command::Start) => {
-                let keyboard = keyboard_day();
+                let keyboard = keyboard_day(db.clock());
 
                 // TODO
                 let _ = db.insert_user(msg.chat.id.0, msg.chat.username()).await;
@@ -105,6 +142,15 @@ pub async fn message_handler(bot: Bot, dialogue: MyDialogue, msg: Message, me: M
                 bot.send_message(msg.chat.id, "Выберите день").reply_markup(keyboard).await?;
                 dialogue.update(State::DayOption).await?;
             }
+            Ok(Command::Search(search)) => {
+                search_movies(bot, dialogue, msg.clone(), db, &search).await?;
+            }
+            // a text without a command is a title to search, in a group it's just a message to the others
+            Err(_) if !text.starts_with('/') => {
+                if msg.chat.is_private() {
+                    search_movies(bot, dialogue, msg.clone(), db, text).await?;
+                }
+            }
             Err(_) => {
                 bot.send_message(msg.chat.id, "Команда не найдена!").await?;
                 dialogue.exit().await?;
@@ -114,56 +160,60 @@ pub async fn message_handler(bot: Bot, dialogue: MyDialogue, msg: Message, me: M
     Ok(())
 }
 
-fn callback_get(q: CallbackQuery) -> Res<(MenuCode, String, Message)> {
-    match (q.data, q.message) {
-        (Some(data), Some(message)) => {
-            let (menu_code, menu_option) = callback_parse(&data)?;
-            Ok((menu_code, menu_option, message))
+// every button carries what it does (see tg::callback), the dialogue only knows the card pinned under a list
+async fn callback_handler(bot: Bot, dialogue: MyDialogue, q: CallbackQuery, db: Arc<dyn ScheduleRepository>) -> Res<()> {
+    let (callback, msg) = match (q.data.as_deref().map(Callback::decode), q.message.clone()) {
+        (Some(Ok(callback)), Some(msg)) => (callback, msg),
+        (Some(Err(e)), _) => {
+            log::warn!("{}", e);
+            return outdated_button(bot, q).await;
         }
-        _ => Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput, "No callback data or message"))),
-    }
-}
-
-async fn callback_handler(bot: Bot, dialogue: MyDialogue, q: CallbackQuery, db: Arc<DB>) -> Res<()> {
-    let (m_code, m_opt, message) = callback_get(q.clone())?;
-    let state: Option<State> = dialogue.get().await?;
-
-    match state {
-        Some(State::DayOption) => {
+        _ => {
+            error!("No callback data or message");
             bot.answer_callback_query(q.id).await?;
-            m_code.check_complience(MenuCode::ChooseDay)?;
+            return Ok(());
+        }
+    };
 
-            cb_handle_day_option(m_opt, message, bot, dialogue).await?;
+    match callback {
+        Callback::Days => {
+            bot.answer_callback_query(q.id).await?;
+            restart_dayoption(bot, dialogue, msg, db).await?;
         }
-        Some(State::StartOption { date }) => {
+        Callback::Main { date, badge } => {
             bot.answer_callback_query(q.id).await?;
-            m_code.check_complience(MenuCode::MainMenu)?;
-
-            let option = m_opt.parse::<i32>()?;
-            cb_handle_start_option(bot, dialogue, message, db, option, date).await?;
+            close_pinned_movie(&bot, &dialogue, &msg).await;
+            restart_mainmenu(bot, dialogue, msg, db, date, badge).await?;
         }
-        Some(State::Cinemas { date }) => {
+        Callback::Cinemas { date, badge } => {
             bot.answer_callback_query(q.id).await?;
-            m_code.check_complience(MenuCode::Cinemas)?;
-
-            let option = m_opt.parse::<i32>()?;
-            cb_handle_cinemas(bot, dialogue, message, db, option, date).await?;
+            close_pinned_movie(&bot, &dialogue, &msg).await;
+            cb_handle_cinemas(bot, dialogue, msg, db, date, badge).await?;
         }
-        Some(State::FromCinema { data }) => {
-            m_code.check_complience(MenuCode::MovielistFromCinema)?;
-
-            let option = m_opt.parse::<i32>()?;
-            cb_handle_pressed_button(bot, dialogue, message, q, db, option, data).await?;
+        Callback::Movies { list, page } => match movielist_state(&dialogue, db.as_ref(), list, msg.id).await? {
+            Some(State::FromCinema { data }) => cb_handle_movies(bot, dialogue, q, msg, db, page, data).await?,
+            Some(State::FromMovie { data }) => cb_handle_movies(bot, dialogue, q, msg, db, page, data).await?,
+            _ => outdated_button(bot, q).await?,
+        },
+        Callback::Movie { schedule, movie_id } => match movielist_state(&dialogue, db.as_ref(), MovieList::new(schedule), msg.id).await? {
+            Some(State::FromCinema { data }) => cb_handle_movie(bot, dialogue, q, msg, db, movie_id, data).await?,
+            Some(State::FromMovie { data }) => cb_handle_movie(bot, dialogue, q, msg, db, movie_id, data).await?,
+            _ => outdated_button(bot, q).await?,
+        },
+        Callback::Sessions { schedule, movie_id } => match movielist_state(&dialogue, db.as_ref(), MovieList::new(schedule), msg.id).await?
+        {
+            Some(State::FromCinema { data }) => cb_handle_sessions(bot, q, db, movie_id, data).await?,
+            Some(State::FromMovie { data }) => cb_handle_sessions(bot, q, db, movie_id, data).await?,
+            _ => outdated_button(bot, q).await?,
+        },
+        Callback::Timetable { schedule, movie_id, page } => {
+            cb_handle_timetable(bot, q, msg, db, schedule, movie_id, page).await?;
         }
-        Some(State::FromMovie { data }) => {
-            m_code.check_complience(MenuCode::MovielistDefault)?;
-
-            let option = m_opt.parse::<i32>()?;
-            cb_handle_pressed_button(bot, dialogue, message, q, db, option, data).await?;
+        Callback::Close { also } => {
+            cb_handle_close(bot, dialogue, q, msg, also).await?;
         }
-        _ => {
-            error!("Couldn't get State");
-            dialogue.exit().await?;
+        Callback::Noop => {
+            bot.answer_callback_query(q.id).await?;
         }
     }
 
@@ -16,7 +16,10 @@ use super::*;
 // };
 // use std::sync::Arc;
 
+pub mod callback;
 pub mod callback_handler;
 pub mod callbackdata;
+pub mod inline;
 pub mod keyboard;
-pub mod tools;
+pub mod storage;
+pub mod text;
@@ -0,0 +1,379 @@
+use super::*;
+use db::MovieSort;
+
+// the format of the callback data, a button of another version (or of the old "{MenuCode}:{value}" format)
+// is reported as outdated (2: the searched text is replaced by its id)
+const VERSION: &str = "2";
+const DELIMITER: char = ':';
+// telegram limit of the callback data, in bytes
+pub const CALLBACK_MAX_LEN: usize = 64;
+
+// which sessions are listed: the date, the filter of the sessions and the cinema (all the cinemas if None)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Schedule {
+    pub date: NaiveDate,
+    pub badge: Option<Badge>,
+    pub cinema_id: Option<i32>,
+}
+
+// the movie list of the schedule: its order and the id of the searched text (moskino.searches)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MovieList {
+    pub schedule: Schedule,
+    pub sort: MovieSort,
+    pub search: Option<i32>,
+}
+
+impl MovieList {
+    pub fn new(schedule: Schedule) -> Self {
+        Self {
+            schedule,
+            sort: MovieSort::default(),
+            search: None,
+        }
+    }
+}
+
+// the page of the movie list: its number (to show) and the movie it starts after or before,
+// the title of the movie is taken from the db, so the cursor fits the callback data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Page {
+    First,
+    After { number: i64, key: i64, movie_id: i32 },
+    Before { number: i64, key: i64, movie_id: i32 },
+}
+
+// what a pressed button does, with everything needed to do it,
+// so the buttons work without the dialogue (e.g. after a restart of the bot)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Callback {
+    // Выберите день
+    Days,
+    // Выберите опцию (the filter of the sessions is toggled by the same callback with another badge)
+    Main { date: NaiveDate, badge: Option<Badge> },
+    // Выберите кинотеатр
+    Cinemas { date: NaiveDate, badge: Option<Badge> },
+    Movies { list: MovieList, page: Page },
+    // the card of the movie under the list
+    Movie { schedule: Schedule, movie_id: i32 },
+    // the sessions of the movie on the card
+    Sessions { schedule: Schedule, movie_id: i32 },
+    // a page of the sessions of the movie across the cinemas
+    Timetable { schedule: Schedule, movie_id: i32, page: i64 },
+    // the message is deleted, and also the other one (e.g. the list of the card)
+    Close { also: Option<MessageId> },
+    // e.g. "1 из 9" or the current order of the list
+    Noop,
+}
+
+impl Callback {
+    // "2:l:231223:s:100:w:a2.1703354400.4521:" - version, action, fields
+    pub fn encode(&self) -> String {
+        let fields = match self {
+            Callback::Days => vec!["d".to_string()],
+            Callback::Main { date, badge } => vec!["m".to_string(), encode_date(*date), encode_badge(*badge)],
+            Callback::Cinemas { date, badge } => vec!["c".to_string(), encode_date(*date), encode_badge(*badge)],
+            Callback::Movies { list, page } => {
+                let mut fields = vec!["l".to_string()];
+                fields.extend(encode_schedule(&list.schedule));
+                fields.push(encode_sort(list.sort));
+                fields.push(encode_page(*page));
+                fields.push(list.search.map(|id| id.to_string()).unwrap_or_default());
+                fields
+            }
+            Callback::Movie { schedule, movie_id } => {
+                let mut fields = vec!["f".to_string()];
+                fields.extend(encode_schedule(schedule));
+                fields.push(movie_id.to_string());
+                fields
+            }
+            Callback::Sessions { schedule, movie_id } => {
+                let mut fields = vec!["s".to_string()];
+                fields.extend(encode_schedule(schedule));
+                fields.push(movie_id.to_string());
+                fields
+            }
+            Callback::Timetable { schedule, movie_id, page } => {
+                let mut fields = vec!["p".to_string()];
+                fields.extend(encode_schedule(schedule));
+                fields.push(movie_id.to_string());
+                fields.push(page.to_string());
+                fields
+            }
+            Callback::Close { also: None } => vec!["x".to_string()],
+            Callback::Close { also: Some(id) } => vec!["x".to_string(), id.0.to_string()],
+            Callback::Noop => vec!["n".to_string()],
+        };
+
+        let data = format!("{}{}{}", VERSION, DELIMITER, fields.join(&DELIMITER.to_string()));
+        debug_assert!(data.len() <= CALLBACK_MAX_LEN, "{}", data);
+        data
+    }
+
+    pub fn decode(data: &str) -> Res<Self> {
+        Self::parse(data).ok_or_else(|| -> Errr {
+            Box::new(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid callback_data format: [{}]", data),
+            ))
+        })
+    }
+
+    fn parse(data: &str) -> Option<Self> {
+        let mut fields = data.split(DELIMITER);
+        if fields.next() != Some(VERSION) {
+            return None;
+        }
+        let action = fields.next()?;
+        let fields: Vec<&str> = fields.collect();
+
+        let callback = match (action, fields.as_slice()) {
+            ("d", []) => Callback::Days,
+            ("m", [date, badge]) => Callback::Main {
+                date: decode_date(date)?,
+                badge: decode_badge(badge)?,
+            },
+            ("c", [date, badge]) => Callback::Cinemas {
+                date: decode_date(date)?,
+                badge: decode_badge(badge)?,
+            },
+            ("l", [date, badge, cinema_id, sort, page, search]) => Callback::Movies {
+                list: MovieList {
+                    schedule: decode_schedule(date, badge, cinema_id)?,
+                    sort: decode_sort(sort)?,
+                    search: match *search {
+                        "" => None,
+                        id => Some(id.parse().ok()?),
+                    },
+                },
+                page: decode_page(page)?,
+            },
+            ("f", [date, badge, cinema_id, movie_id]) => Callback::Movie {
+                schedule: decode_schedule(date, badge, cinema_id)?,
+                movie_id: movie_id.parse().ok()?,
+            },
+            ("s", [date, badge, cinema_id, movie_id]) => Callback::Sessions {
+                schedule: decode_schedule(date, badge, cinema_id)?,
+                movie_id: movie_id.parse().ok()?,
+            },
+            ("p", [date, badge, cinema_id, movie_id, page]) => Callback::Timetable {
+                schedule: decode_schedule(date, badge, cinema_id)?,
+                movie_id: movie_id.parse().ok()?,
+                page: page.parse().ok()?,
+            },
+            ("x", []) => Callback::Close { also: None },
+            ("x", [id]) => Callback::Close {
+                also: Some(MessageId(id.parse().ok()?)),
+            },
+            ("n", []) => Callback::Noop,
+            _ => return None,
+        };
+        Some(callback)
+    }
+}
+
+fn encode_date(date: NaiveDate) -> String {
+    date.format("%y%m%d").to_string()
+}
+
+fn decode_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%y%m%d").ok()
+}
+
+fn encode_badge(badge: Option<Badge>) -> String {
+    match badge {
+        None => "-",
+        Some(Badge::TwoD) => "2",
+        Some(Badge::ThreeD) => "3",
+        Some(Badge::Subtitles) => "s",
+        Some(Badge::Original) => "o",
+        Some(Badge::Pushkin) => "p",
+    }
+    .to_string()
+}
+
+// the outer None is a broken field, the inner one is no filter
+fn decode_badge(badge: &str) -> Option<Option<Badge>> {
+    match badge {
+        "-" => Some(None),
+        "2" => Some(Some(Badge::TwoD)),
+        "3" => Some(Some(Badge::ThreeD)),
+        "s" => Some(Some(Badge::Subtitles)),
+        "o" => Some(Some(Badge::Original)),
+        "p" => Some(Some(Badge::Pushkin)),
+        _ => None,
+    }
+}
+
+fn encode_schedule(schedule: &Schedule) -> [String; 3] {
+    [
+        encode_date(schedule.date),
+        encode_badge(schedule.badge),
+        schedule.cinema_id.map(|id| id.to_string()).unwrap_or_default(),
+    ]
+}
+
+fn decode_schedule(date: &str, badge: &str, cinema_id: &str) -> Option<Schedule> {
+    Some(Schedule {
+        date: decode_date(date)?,
+        badge: decode_badge(badge)?,
+        cinema_id: match cinema_id {
+            "" => None,
+            id => Some(id.parse().ok()?),
+        },
+    })
+}
+
+fn encode_sort(sort: MovieSort) -> String {
+    match sort {
+        MovieSort::Title => "t",
+        MovieSort::Showtime => "w",
+        MovieSort::Sessions => "n",
+        MovieSort::Release => "r",
+        MovieSort::Relevance => "q",
+    }
+    .to_string()
+}
+
+fn decode_sort(sort: &str) -> Option<MovieSort> {
+    match sort {
+        "t" => Some(MovieSort::Title),
+        "w" => Some(MovieSort::Showtime),
+        "n" => Some(MovieSort::Sessions),
+        "r" => Some(MovieSort::Release),
+        "q" => Some(MovieSort::Relevance),
+        _ => None,
+    }
+}
+
+// "" - the first page, "a2.1703354400.4521" - the 2nd page after the movie 4521 with the key
+fn encode_page(page: Page) -> String {
+    match page {
+        Page::First => String::new(),
+        Page::After { number, key, movie_id } => format!("a{}.{}.{}", number, key, movie_id),
+        Page::Before { number, key, movie_id } => format!("b{}.{}.{}", number, key, movie_id),
+    }
+}
+
+fn decode_page(page: &str) -> Option<Page> {
+    if page.is_empty() {
+        return Some(Page::First);
+    }
+
+    let (after, cursor) = match (page.strip_prefix('a'), page.strip_prefix('b')) {
+        (Some(cursor), _) => (true, cursor),
+        (_, Some(cursor)) => (false, cursor),
+        _ => return None,
+    };
+    let mut parts = cursor.split('.');
+    let (number, key, movie_id) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(number), Some(key), Some(movie_id), None) => (number.parse().ok()?, key.parse().ok()?, movie_id.parse().ok()?),
+        _ => return None,
+    };
+
+    if after {
+        Some(Page::After { number, key, movie_id })
+    } else {
+        Some(Page::Before { number, key, movie_id })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_callback_codec() {
+        let date = NaiveDate::from_ymd_opt(2023, 12, 23).unwrap();
+        let schedule = Schedule {
+            date,
+            badge: Some(Badge::Subtitles),
+            cinema_id: Some(100),
+        };
+        let list = MovieList {
+            schedule,
+            sort: MovieSort::Showtime,
+            search: None,
+        };
+
+        let callbacks = vec![
+            Callback::Days,
+            Callback::Main { date, badge: None },
+            Callback::Cinemas {
+                date,
+                badge: Some(Badge::Pushkin),
+            },
+            Callback::Movies { list, page: Page::First },
+            Callback::Movies {
+                list,
+                page: Page::Before {
+                    number: 2,
+                    key: -i64::MAX,
+                    movie_id: i32::MAX,
+                },
+            },
+            Callback::Movie { schedule, movie_id: 4521 },
+            Callback::Sessions { schedule, movie_id: 4521 },
+            Callback::Timetable {
+                schedule,
+                movie_id: 4521,
+                page: 2,
+            },
+            Callback::Close { also: None },
+            Callback::Close { also: Some(MessageId(7)) },
+            Callback::Noop,
+        ];
+        for callback in callbacks {
+            let data = callback.encode();
+            assert!(data.len() <= CALLBACK_MAX_LEN, "{}", data);
+            assert_eq!(Callback::decode(&data).unwrap(), callback);
+        }
+
+        assert_eq!(
+            Callback::Movies {
+                list,
+                page: Page::After {
+                    number: 2,
+                    key: 1703354400,
+                    movie_id: 4521
+                }
+            }
+            .encode(),
+            "2:l:231223:s:100:w:a2.1703354400.4521:"
+        );
+
+        // the old format and other versions
+        assert!(Callback::decode("2:-97").is_err());
+        assert!(Callback::decode("1:m:231223:-").is_err());
+        assert!(Callback::decode("2:m:231223").is_err());
+        assert!(Callback::decode("2:l:231223:-::t:Ж:").is_err());
+    }
+
+    #[test]
+    fn test_callback_search() {
+        let list = MovieList {
+            schedule: Schedule {
+                date: NaiveDate::from_ymd_opt(2023, 12, 23).unwrap(),
+                badge: None,
+                cinema_id: Some(100),
+            },
+            sort: MovieSort::Relevance,
+            search: Some(1_000_000),
+        };
+        let callback = Callback::Movies {
+            list,
+            page: Page::After {
+                number: 99,
+                key: 1703354400,
+                movie_id: 100_000,
+            },
+        };
+        let data = callback.encode();
+        assert!(data.len() <= CALLBACK_MAX_LEN, "{}", data);
+        assert_eq!(Callback::decode(&data).unwrap(), callback);
+
+        // a button with the text itself (the previous version) or a broken id
+        assert!(Callback::decode("1:l:231223:-::q::елки: новые").is_err());
+        assert!(Callback::decode("2:l:231223:-::q::елки").is_err());
+    }
+}
@@ -1,16 +1,50 @@
 use super::*;
+use callback::*;
+use text::*;
 
-pub async fn restart_mainmenu(bot: Bot, dialogue: MyDialogue, msg: Message, date: NaiveDate) -> Res<()> {
-    let keyboard = keyboard_main();
-    bot.edit_message_text(msg.chat.id, msg.id, "Выберите опцию")
-        .reply_markup(keyboard)
-        .await?;
-    dialogue.update(State::StartOption { date }).await?;
+// "Выберите опцию" and when the schedule of the date was updated by web-parser
+async fn mainmenu_text(db: &dyn ScheduleRepository, date: NaiveDate, badge: Option<Badge>) -> String {
+    let text = match db.q_get_latest_run_by_date(date).await {
+        Ok(Some(run)) => {
+            let updated_at = run.finished_at.unwrap_or(run.started_at);
+            let updated_at = updated_at.with_timezone(&MOSCOW);
+
+            if updated_at.date_naive() == db.clock().now_msk().date_naive() {
+                format!("Выберите опцию\n\nРасписание обновлено в {}", updated_at.format("%H:%M"))
+            } else {
+                format!("Выберите опцию\n\nРасписание обновлено {}", updated_at.format("%d.%m в %H:%M"))
+            }
+        }
+        Ok(None) => "Выберите опцию\n\nРасписание на этот день еще не загружено".to_string(),
+        Err(e) => {
+            error!("q_get_latest_run_by_date: {:?}", e);
+            "Выберите опцию".to_string()
+        }
+    };
+
+    match badge {
+        Some(badge) => format!("{}\nТолько сеансы: {}", text, badge.label()),
+        None => text,
+    }
+}
+
+pub async fn restart_mainmenu(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    db: Arc<dyn ScheduleRepository>,
+    date: NaiveDate,
+    badge: Option<Badge>,
+) -> Res<()> {
+    let keyboard = keyboard_main(date, badge);
+    let text = mainmenu_text(db.as_ref(), date, badge).await;
+    bot.edit_message_text(msg.chat.id, msg.id, text).reply_markup(keyboard).await?;
+    dialogue.update(State::StartOption { date, badge }).await?;
     Ok(())
 }
 
-pub async fn restart_dayoption(bot: Bot, dialogue: MyDialogue, msg: Message) -> Res<()> {
-    let keyboard = keyboard_day();
+pub async fn restart_dayoption(bot: Bot, dialogue: MyDialogue, msg: Message, db: Arc<dyn ScheduleRepository>) -> Res<()> {
+    let keyboard = keyboard_day(db.clock());
 
     bot.edit_message_text(msg.chat.id, msg.id, "Выберите день")
         .reply_markup(keyboard)
@@ -19,178 +53,270 @@ pub async fn restart_dayoption(bot: Bot, dialogue: MyDialogue, msg: Message) ->
     Ok(())
 }
 
-pub async fn cb_handle_day_option(raw_option: String, msg: Message, bot: Bot, dialogue: MyDialogue) -> Res<()> {
-    match raw_option.parse::<i32>() {
-        Ok(option) => match option.try_into() {
-            Ok(ButtonOption::Close) => {
-                bot.delete_message(msg.chat.id, msg.id).await?;
-                dialogue.exit().await?;
-            }
-            _ => {
-                error!("E! option: {}, dialogue: {:?}", option, dialogue);
-                dialogue.exit().await?;
-            }
-        },
-        Err(_) => match NaiveDate::parse_from_str(&raw_option, "%Y.%m.%d") {
-            Ok(date) => {
-                let keyboard = keyboard_main();
-                bot.edit_message_text(msg.chat.id, msg.id, "Выберите опцию")
-                    .reply_markup(keyboard)
-                    .await?;
-                dialogue.update(State::StartOption { date }).await?;
-            }
-            Err(_) => {
-                error!("E! option: {}, dialogue: {:?}", raw_option, dialogue);
-                dialogue.exit().await?;
-            }
-        },
+// a title is not longer than that
+const SEARCH_MAX_CHARS: usize = 100;
+
+// /search <text> or just a text: today's movies found by the title are listed as 'All movies' are
+pub async fn search_movies(bot: Bot, dialogue: MyDialogue, msg: Message, db: Arc<dyn ScheduleRepository>, text: &str) -> Res<()> {
+    let text = text.trim();
+
+    if text.chars().count() < 2 {
+        bot.send_message(msg.chat.id, "Напишите название фильма, например: /search Олдбой")
+            .await?;
+        return Ok(());
     }
+    if text.chars().count() > SEARCH_MAX_CHARS {
+        bot.send_message(
+            msg.chat.id,
+            format!("Слишком длинное название, напишите не больше {} символов", SEARCH_MAX_CHARS),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let sent = bot.send_message(msg.chat.id, format!("Ищу «{}»…", text)).await?;
+
+    let search = Search {
+        id: db.insert_search(text).await?,
+        text: text.to_string(),
+    };
+    let data = CallbackDataDefault::search(db.clock().cinema_today(), search, sent.id, *DB_ITEMS_PER_PAGE);
+    callback_handle_movielist(bot, dialogue, sent, db, data).await
+}
+
+// a button of an old version, or the cinema of the list is gone
+pub async fn outdated_button(bot: Bot, q: CallbackQuery) -> Res<()> {
+    bot.answer_callback_query(q.id)
+        .text("Меню устарело, начните заново: /start")
+        .show_alert(true)
+        .await?;
     Ok(())
 }
 
-pub async fn cb_handle_start_option(bot: Bot, dialogue: MyDialogue, msg: Message, db: Arc<DB>, option: i32, date: NaiveDate) -> Res<()> {
-    match option.try_into() {
-        // option 'Close' selected on the start menu
-        Ok(ButtonOption::Close) => {
-            bot.delete_message(msg.chat.id, msg.id).await?;
-            dialogue.exit().await?;
+// the card pinned under the list is known from the dialogue only (the buttons of the list don't have it)
+async fn pinned_movie(dialogue: &MyDialogue, id_list_msg: MessageId) -> Option<CallbackPinnedMsg> {
+    match dialogue.get().await {
+        Ok(Some(State::FromCinema { data })) if data.id_msg == id_list_msg => data.pinned_msg,
+        Ok(Some(State::FromMovie { data })) if data.id_msg == id_list_msg => data.pinned_msg,
+        Ok(_) => None,
+        Err(e) => {
+            error!("dialogue.get: {:?}", e);
+            None
         }
-        Ok(ButtonOption::Up) => {
-            restart_dayoption(bot, dialogue, msg).await?;
+    }
+}
+
+// the list is left, so its card is deleted too
+pub async fn close_pinned_movie(bot: &Bot, dialogue: &MyDialogue, msg: &Message) {
+    if let Some(pinned_msg) = pinned_movie(dialogue, msg.id).await {
+        // the card may be already closed
+        let _ = bot.delete_message(msg.chat.id, pinned_msg.id_msg).await;
+    }
+}
+
+// the data of the movie list the button belongs to: the cinema and the searched text are taken from the db again
+// (None if they are gone)
+pub async fn movielist_state(dialogue: &MyDialogue, db: &dyn ScheduleRepository, list: MovieList, id_msg: MessageId) -> Res<Option<State>> {
+    let pinned_msg = pinned_movie(dialogue, id_msg).await;
+    let Schedule { date, badge, cinema_id } = list.schedule;
+
+    let state = match cinema_id {
+        Some(cinema_id) => match db.q_get_cinema_by_id(cinema_id).await? {
+            Some(cinema) => State::FromCinema {
+                data: CallbackData {
+                    sort: list.sort,
+                    ..CallbackDataCinema::new(date, badge, cinema, id_msg, pinned_msg, *DB_ITEMS_PER_PAGE)
+                },
+            },
+            None => return Ok(None),
+        },
+        None => {
+            let search = match list.search {
+                Some(id) => match db.q_get_search(id).await? {
+                    Some(text) => Some(Search { id, text }),
+                    None => return Ok(None),
+                },
+                None => None,
+            };
+
+            State::FromMovie {
+                data: CallbackData {
+                    sort: list.sort,
+                    search,
+                    ..CallbackDataDefault::new(date, badge, id_msg, pinned_msg, *DB_ITEMS_PER_PAGE)
+                },
+            }
         }
-        // option 'All movies' selected
-        Ok(ButtonOption::Movies) => {
-            // initialize 'raw' CallbackData for callback_handle_movielist
-            let data = CallbackDataDefault::new(date, msg.id, None, *DB_ITEMS_PER_PAGE);
-            callback_handle_movielist(bot, dialogue, msg, db, ButtonOption::NotSetted, data).await?;
+    };
+    Ok(Some(state))
+}
+
+pub async fn cb_handle_close(bot: Bot, dialogue: MyDialogue, q: CallbackQuery, msg: Message, also: Option<MessageId>) -> Res<()> {
+    bot.answer_callback_query(q.id).await?;
+
+    close_pinned_movie(&bot, &dialogue, &msg).await;
+    bot.delete_message(msg.chat.id, msg.id).await?;
+    if let Some(id_msg) = also {
+        // the list of the card may be already closed
+        let _ = bot.delete_message(msg.chat.id, id_msg).await;
+    }
+    dialogue.exit().await?;
+    Ok(())
+}
+
+// option 'By cinema' selected
+pub async fn cb_handle_cinemas(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    db: Arc<dyn ScheduleRepository>,
+    date: NaiveDate,
+    badge: Option<Badge>,
+) -> Res<()> {
+    let cinemas = db.q_get_cinemas().await;
+
+    match cinemas {
+        Ok(Some(cinemas)) => {
+            let text = cinemas_text(&cinemas);
+            let keyboard = keyboard_cinemas(cinemas, date, badge);
+            bot.edit_message_text(msg.chat.id, msg.id, text).reply_markup(keyboard).await?;
+            dialogue.update(State::Cinemas { date, badge }).await?;
         }
-        // option 'By cinema' selected
-        Ok(ButtonOption::Cinemas) => {
-            let cinemas = DB::q_get_cinemas(&db.conn).await;
-
-            match cinemas {
-                Ok(Some(cinemas)) => {
-                    let keyboard = keyboard_cinemas(cinemas);
-                    bot.edit_message_text(msg.chat.id, msg.id, "Выберите кинотеатр")
-                        .reply_markup(keyboard)
-                        .await?;
-                    dialogue.update(State::Cinemas { date }).await?;
-                }
-                Ok(None) => {
-                    let keyboard = keyboard_ok_or_up(MenuCode::Cinemas);
-                    bot.edit_message_text(msg.chat.id, msg.id, "Нету доступных кинотеатров для показа")
-                        .reply_markup(keyboard)
-                        .await?;
-                    dialogue.update(State::Cinemas { date }).await?;
-                }
-                Err(e) => {
-                    error!("q_get_cinemas: {:?}", e);
-                    bot.edit_message_text(msg.chat.id, msg.id, "Что-то пошло не так").await?;
-                    dialogue.exit().await?;
-                }
-            }
+        Ok(None) => {
+            let keyboard = keyboard_ok_or_up(Callback::Main { date, badge });
+            bot.edit_message_text(msg.chat.id, msg.id, "Нету доступных кинотеатров для показа")
+                .reply_markup(keyboard)
+                .await?;
+            dialogue.update(State::Cinemas { date, badge }).await?;
         }
-        _ => {
-            error!("E! option: {}, dialogue: {:?}", option, dialogue);
+        Err(e) => {
+            error!("q_get_cinemas: {:?}", e);
+            bot.edit_message_text(msg.chat.id, msg.id, "Что-то пошло не так").await?;
             dialogue.exit().await?;
         }
     }
     Ok(())
 }
 
-pub async fn cb_handle_cinemas(bot: Bot, dialogue: MyDialogue, msg: Message, db: Arc<DB>, option: i32, date: NaiveDate) -> Res<()> {
-    match option.try_into() {
-        Ok(option) => match option {
-            ButtonOption::Close => {
-                bot.delete_message(msg.chat.id, msg.id).await?;
-                dialogue.exit().await?;
-            }
-            ButtonOption::Up => {
-                restart_mainmenu(bot, dialogue, msg, date).await?;
-            }
-            _ => {
-                error!("What am I doing here");
-                dialogue.exit().await?;
-            }
-        },
-        _ => match option {
-            cinema_id if cinema_id > 0 => {
-                let cinema_name = DB::q_get_cinema_name_by_id(&db.conn, cinema_id).await;
-
-                match cinema_name {
-                    Ok(Some(cinema_name)) => {
-                        let cinema = Cinema::new(cinema_id, cinema_name);
-                        let data = CallbackDataCinema::new(date, cinema, msg.id, None, *DB_ITEMS_PER_PAGE);
-                        callback_handle_movielist(bot, dialogue, msg, db, ButtonOption::NotSetted, data).await?;
-                    }
-                    Ok(None) => {
-                        error!("q_get_cinema_name_by_id");
-
-                        bot.edit_message_text(msg.chat.id, msg.id, "Что-то пошло не так").await?;
-                        dialogue.exit().await?;
-                    }
-                    Err(e) => {
-                        error!("q_get_cinema_name_by_id: {:?}", e);
-
-                        bot.edit_message_text(msg.chat.id, msg.id, "Что-то пошло не так").await?;
-                        dialogue.exit().await?;
-                    }
-                }
-            }
-            _ => {
-                error!("E! option: {}, dialogue: {:?}", option, dialogue);
-                dialogue.exit().await?;
-            }
-        },
+// Выберите кинотеатр
+//
+// Сатурн: Снежная ул., д. 18 • 🟠 Свиблово
+// ...
+fn cinemas_text(cinemas: &[Cinema]) -> String {
+    let locations = cinemas
+        .iter()
+        .filter_map(|cinema| cinema.location().map(|location| format!("{}: {}", cinema.name, location)))
+        .collect::<Vec<String>>();
+
+    if locations.is_empty() {
+        "Выберите кинотеатр".to_string()
+    } else {
+        format!("Выберите кинотеатр\n\n{}", locations.join("\n"))
     }
-    Ok(())
 }
 
-pub async fn cb_handle_pressed_button<T>(
+// a page of the movie list, e.g. the next one or the first one in another order
+pub async fn cb_handle_movies<T>(
     bot: Bot,
     dialogue: MyDialogue,
-    msg: Message,
     q: CallbackQuery,
-    db: Arc<DB>,
-    option: i32,
+    msg: Message,
+    db: Arc<dyn ScheduleRepository>,
+    page: Page,
     mut data: CallbackData<T>,
 ) -> Res<()>
 where
     CallbackData<T>: Cbd,
 {
-    match option.try_into() {
-        Ok(option) => match option {
-            ButtonOption::Back | ButtonOption::Forward => {
-                bot.answer_callback_query(q.id).await?;
-                callback_handle_movielist(bot, dialogue, msg, db, option, data).await?;
-            }
-            ButtonOption::Close => {
-                bot.delete_message(msg.chat.id, msg.id).await?;
-                bot.delete_message(msg.chat.id, data.id_msg).await?;
-                dialogue.exit().await?;
-            }
-            ButtonOption::Sessions => {
-                let sessions = data.q_get_sessions(&db.conn).await?;
-                data.show_sessions(bot, q, sessions).await?;
-            }
-            ButtonOption::Up => {
-                bot.answer_callback_query(q.id).await?;
-                data.go_prev(bot, dialogue, msg, db).await?;
-            }
-            _ => {
-                // TODO
-            }
-        },
-        _ => match option {
-            // some movie selected on the page
-            db_movie_id if db_movie_id > 0 => {
-                bot.answer_callback_query(q.id).await?;
-                callback_handle_pinned_movie(bot, dialogue, msg, db, db_movie_id, data).await?;
-            }
-            _ => {
-                log::warn!("------------------------------- WHAT AM I DOING HERE {option}");
-            }
-        },
+    bot.answer_callback_query(q.id).await?;
+
+    data.set_page(db.as_ref(), page).await?;
+    callback_handle_movielist(bot, dialogue, msg, db, data).await
+}
+
+// some movie selected on the page
+pub async fn cb_handle_movie<T>(
+    bot: Bot,
+    dialogue: MyDialogue,
+    q: CallbackQuery,
+    msg: Message,
+    db: Arc<dyn ScheduleRepository>,
+    movie_id: i32,
+    data: CallbackData<T>,
+) -> Res<()>
+where
+    CallbackData<T>: Cbd,
+{
+    callback_handle_pinned_movie(bot, dialogue, q, msg, db, movie_id, data).await
+}
+
+// 🗓 on the card
+pub async fn cb_handle_sessions<T>(
+    bot: Bot,
+    q: CallbackQuery,
+    db: Arc<dyn ScheduleRepository>,
+    movie_id: i32,
+    data: CallbackData<T>,
+) -> Res<()>
+where
+    CallbackData<T>: Cbd,
+{
+    data.show_sessions(bot, q, db.as_ref(), movie_id).await
+}
+
+// the page of the sessions of the movie in the cinema of the schedule or across the cinemas, None if there are no upcoming ones
+pub async fn timetable(
+    db: &dyn ScheduleRepository,
+    schedule: Schedule,
+    movie_id: i32,
+    page: i64,
+) -> Res<Option<(String, InlineKeyboardMarkup)>> {
+    let Some(movie) = db.q_get_movie_by_id(movie_id).await? else {
+        return Ok(None);
+    };
+    let now = db.clock().now();
+    let sessions = match schedule.cinema_id {
+        Some(cinema_id) => {
+            db.q_get_sessions_by_cinema(movie_id, cinema_id, schedule.date, now, schedule.badge)
+                .await?
+        }
+        None => db.q_get_sessions_all(movie_id, schedule.date, now, schedule.badge).await?,
+    };
+    let Some(sessions) = sessions else {
+        return Ok(None);
+    };
+
+    let headline = timetable_headline(&movie.title, schedule.date, db.clock().cinema_today(), schedule.badge);
+    let pages = timetable_pages(&headline, &sessions, MESSAGE_MAX_LEN);
+
+    // the sessions are over while the pages were turned
+    let total_pages = pages.len() as i64;
+    let page = page.clamp(1, total_pages);
+
+    let keyboard = keyboard_timetable(schedule, movie_id, page, total_pages);
+    Ok(Some((pages[page as usize - 1].clone(), keyboard)))
+}
+
+pub async fn cb_handle_timetable(
+    bot: Bot,
+    q: CallbackQuery,
+    msg: Message,
+    db: Arc<dyn ScheduleRepository>,
+    schedule: Schedule,
+    movie_id: i32,
+    page: i64,
+) -> Res<()> {
+    match timetable(db.as_ref(), schedule, movie_id, page).await? {
+        Some((text, keyboard)) => {
+            bot.answer_callback_query(q.id).await?;
+            bot.edit_message_text(msg.chat.id, msg.id, text).reply_markup(keyboard).await?;
+        }
+        None => {
+            bot.answer_callback_query(q.id)
+                .text("Нету доступных сеансов")
+                .show_alert(true)
+                .await?;
+        }
     }
     Ok(())
 }
@@ -200,43 +326,37 @@ pub async fn callback_handle_movielist<T>(
     bot: Bot,
     dialogue: MyDialogue,
     msg: Message,
-    db: Arc<DB>,
-    option: ButtonOption,
+    db: Arc<dyn ScheduleRepository>,
     mut data: CallbackData<T>,
 ) -> Res<()>
 where
     CallbackData<T>: Cbd,
 {
-    // if the function was called when clicking the next or previous movie page,
-    // then it is necessary to check if we can do this, otherwise do nothing
-    if !data.set_current_page(option) {
-        return Ok(());
-    }
-
-    // TODO:
-    // Проверять количество страниц с предыдущим значением
-    // если количество страниц изменилось - значит поступили новые данные, или наоборот
-    // ЕСЛИ ВЫВЕДЕН КАКОЙ-ТО ФИЛЬМ, ТО ИЛИ УДАЛИТЬ ИЛИ ПРОВЕРИТЬ, ЧТО ОН ЕЩЕ АКТУАЛЕЕН (В ПРОТИВНОМ СЛУЧАЕ УДАЛИТЬ)
-
-    let mut tx = db.conn.begin().await?;
+    // the count and the page are queried for the same `now` (not in one snapshot: a scrape in between may change them)
+    let now = db.clock().now();
 
     // find out the current number of available movies
-    let db_movies_count = data.q_count_movies(&mut *tx).await?;
+    let db_movies_count = data.q_count_movies(db.as_ref(), now).await?;
 
-    // get brief information about movies
-    let movies = data.q_get_movies_short(Arc::clone(&db)).await?;
-
-    tx.commit().await?;
-
-    // check and specify the number of pages
+    // specify the number of pages (the list is shown from the start if it got shorter than the page)
     data.set_total_pages(db_movies_count);
 
+    // get brief information about movies
+    let movies = data.q_get_movies_short(db.as_ref(), now).await?;
+
     match movies {
         Some(movies) => {
             // Выводим список фильмов
-            let keyboard = keyboard_movielist(movies, data.get_menu_code(), data.db_current_page, data.db_total_pages);
+            let keyboard = keyboard_movielist(
+                movies,
+                &data.list(),
+                data.db_current_page,
+                data.db_total_pages,
+                &data.sorts(),
+                data.up(),
+            );
 
-            let text = data.headline_text();
+            let text = data.headline_text(db.clock().cinema_today());
 
             // TODO:
             // не нужно каждый раз менять текст сообщения, нужно только обновлять клавиатуру (вынести первый вывод сообщения из функции)
@@ -244,7 +364,7 @@ where
             bot.edit_message_text(msg.chat.id, data.id_msg, text).reply_markup(keyboard).await?;
         }
         None => {
-            let (headline_text, keyboard) = data.get_data_for_absence_answer();
+            let (headline_text, keyboard) = data.get_data_for_absence_answer(db.clock().cinema_today());
             bot.edit_message_text(msg.chat.id, msg.id, headline_text)
                 .reply_markup(keyboard)
                 .await?;
@@ -259,8 +379,9 @@ where
 pub async fn callback_handle_pinned_movie<T>(
     bot: Bot,
     dialogue: MyDialogue,
+    q: CallbackQuery,
     msg: Message,
-    db: Arc<DB>,
+    db: Arc<dyn ScheduleRepository>,
     db_movie_id: i32,
     mut data: CallbackData<T>,
 ) -> Res<()>
@@ -270,14 +391,27 @@ where
     // if the current movie is out, then there is no need to update the information
     if let Some(pinned_data) = data.pinned_msg {
         if pinned_data.db_id_movie == db_movie_id {
+            bot.answer_callback_query(q.id).await?;
             return Ok(());
         }
     }
 
-    let movie = DB::q_get_movie_by_id(&db.conn, db_movie_id).await?;
+    // the movie list is outdated
+    let Some(movie) = db.q_get_movie_by_id(db_movie_id).await? else {
+        log::warn!("movie_id: {}: not found", db_movie_id);
+        bot.answer_callback_query(q.id).text("Фильм не найден").show_alert(true).await?;
+        return Ok(());
+    };
+    bot.answer_callback_query(q.id).await?;
 
-    let text = movie.description();
-    let keyboard = keybord_movie_links(movie.href_moskino.as_deref(), movie.href_kinopoisk.as_deref(), data.get_menu_code());
+    let text = data.movie_card_text(&movie);
+    let keyboard = keybord_movie_links(
+        movie.href_moskino.as_deref(),
+        movie.href_kinopoisk.as_deref(),
+        data.schedule(),
+        db_movie_id,
+        data.id_msg,
+    );
 
     match data.pinned_msg {
         Some(ref mut pinned_data) => {
@@ -1,14 +1,19 @@
 use super::*;
-use crate::tg::callback_handler::*;
-use db::{tools::datetime_utc3, Cinema, DBResult, MovieShort, Session, DB};
+use callback::*;
+use chrono::{DateTime, Utc};
+use db::{repository::ScheduleRepository, Badge, Cinema, DBResult, Keyset, Movie, MovieCursor, MovieShort, MovieSort, Session};
 use keyboard::*;
+use serde::{Deserialize, Serialize};
+use text::*;
 
 // storing data from callbacks (button clicks)
 // used for State::FromCinema and State::FromMovie states
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CallbackData<T> {
     // selected date
     pub date: NaiveDate,
+    // selected filter of the sessions (e.g. only with subtitles), movies without such sessions are hidden
+    pub badge: Option<Badge>,
     // id a message containing a list of films by cinema
     pub id_msg: MessageId,
     // current movie list page
@@ -17,6 +22,12 @@ pub struct CallbackData<T> {
     pub db_total_pages: i64,
     // volume of a page (how many max movies per page)
     pub db_items_per_page: i64,
+    // order of the movie list
+    pub sort: MovieSort,
+    // where the requested page starts: after the last or before the first movie of the shown page
+    pub keyset: Keyset,
+    // the text searched by the title (/search), only the found movies are listed
+    pub search: Option<Search>,
     // depending on which menu we came from, we will need information about the selected cinema:
     // - if the 'All Movies' button is pressed, we don't need information about any specific cinema.
     // - if the 'By Cinema' button is pressed, and a cinema is subsequently selected, information about the selected cinema will be stored here.
@@ -28,7 +39,14 @@ pub struct CallbackData<T> {
 pub type CallbackDataDefault = CallbackData<()>;
 pub type CallbackDataCinema = CallbackData<Cinema>;
 
-#[derive(Debug, Copy, Clone)]
+// the searched text is kept in the db (moskino.searches), the buttons carry its id
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Search {
+    pub id: i32,
+    pub text: String,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct CallbackPinnedMsg {
     // ID of the message where movie information is displayed
     pub id_msg: MessageId,
@@ -39,60 +57,103 @@ pub struct CallbackPinnedMsg {
 #[async_trait]
 // different realization for CallbackDataCinema and CallbackDataDefault
 pub trait Cbd {
-    fn get_menu_code(&self) -> MenuCode;
+    // the date, the filter and the cinema of the list, put into the buttons
+    fn schedule(&self) -> Schedule;
+    // the menu the list was opened from
+    fn up(&self) -> Callback;
     fn state_update(self) -> State;
-    fn headline_text(&self) -> String;
-    fn get_data_for_absence_answer(&self) -> (String, InlineKeyboardMarkup);
-    async fn show_sessions(&self, bot: Bot, q: CallbackQuery, sessions: Option<Vec<Session>>) -> Res<()>;
-    async fn q_count_movies(&self, conn: impl sqlx::PgExecutor<'_>) -> DBResult<i64>;
-    async fn q_get_movies_short(&self, db: Arc<DB>) -> DBResult<Option<Vec<MovieShort>>>;
-    async fn q_get_sessions(&mut self, conn: impl sqlx::PgExecutor<'_>) -> DBResult<Option<Vec<Session>>>;
-    async fn go_prev(&self, bot: Bot, dialogue: MyDialogue, msg: Message, db: Arc<DB>) -> Res<()>;
+    fn headline_text(&self, today: NaiveDate) -> String;
+    fn movie_card_text(&self, movie: &Movie) -> String;
+    fn get_data_for_absence_answer(&self, today: NaiveDate) -> (String, InlineKeyboardMarkup);
+    // 🗓 on the card of the movie
+    async fn show_sessions(&self, bot: Bot, q: CallbackQuery, db: &dyn ScheduleRepository, movie_id: i32) -> Res<()>;
+    // only the sessions starting not earlier than `now` are taken into account
+    async fn q_count_movies(&self, db: &dyn ScheduleRepository, now: DateTime<Utc>) -> DBResult<i64>;
+    async fn q_get_movies_short(&self, db: &dyn ScheduleRepository, now: DateTime<Utc>) -> DBResult<Option<Vec<MovieShort>>>;
+    async fn q_get_sessions(&self, db: &dyn ScheduleRepository, movie_id: i32, now: DateTime<Utc>) -> DBResult<Option<Vec<Session>>>;
 }
 
 #[async_trait]
 impl Cbd for CallbackDataDefault {
-    async fn go_prev(&self, bot: Bot, dialogue: MyDialogue, msg: Message, _: Arc<DB>) -> Res<()> {
-        if let Some(pinned_msg) = self.pinned_msg {
-            bot.delete_message(msg.chat.id, pinned_msg.id_msg).await?;
-        }
-        restart_mainmenu(bot, dialogue, msg, self.date).await
-    }
-
-    // TODO
-    // выводить сообщение о переходе на сайт только есть список реально не влезает (сделать + проверить длину)
-    #[allow(unused_variables)]
-    async fn show_sessions(&self, bot: Bot, q: CallbackQuery, sessions: Option<Vec<Session>>) -> Res<()> {
-        bot.answer_callback_query(q.id)
-            .text(String::from(
-                "Список может получиться слишком длинным. Посмотрите сеансы перейдя по ссылке на Москино",
-            ))
-            .show_alert(true)
-            .await?;
-        Ok(())
+    fn schedule(&self) -> Schedule {
+        Schedule {
+            date: self.date,
+            badge: self.badge,
+            cinema_id: None,
+        }
     }
 
-    async fn q_count_movies(&self, conn: impl sqlx::PgExecutor<'_>) -> Result<i64, sqlx::Error> {
-        DB::q_count_movies(conn, self.date).await
+    fn up(&self) -> Callback {
+        Callback::Main {
+            date: self.date,
+            badge: self.badge,
+        }
+    }
+
+    // the sessions across all the cinemas are too many for the buttons, so they are sent as a message
+    async fn show_sessions(&self, bot: Bot, q: CallbackQuery, db: &dyn ScheduleRepository, movie_id: i32) -> Res<()> {
+        match (timetable(db, self.schedule(), movie_id, 1).await?, q.message) {
+            (Some((text, keyboard)), Some(msg)) => {
+                bot.answer_callback_query(q.id).await?;
+                bot.send_message(msg.chat.id, text).reply_markup(keyboard).await?;
+            }
+            _ => {
+                bot.answer_callback_query(q.id)
+                    .text("Нету доступных сеансов")
+                    .show_alert(true)
+                    .await?;
+            }
+        }
+        Ok(())
     }
 
-    // TODO
-    // unwrap
-    async fn q_get_sessions(&mut self, conn: impl sqlx::PgExecutor<'_>) -> DBResult<Option<Vec<Session>>> {
-        DB::q_get_sessions_all(conn, self.pinned_msg.unwrap().db_id_movie, self.date).await
+    async fn q_count_movies(&self, db: &dyn ScheduleRepository, now: DateTime<Utc>) -> DBResult<i64> {
+        match &self.search {
+            Some(search) => db.q_count_search_movies(&search.text, self.date, now, self.badge).await,
+            None => db.q_count_movies(self.date, now, self.badge).await,
+        }
     }
 
-    async fn q_get_movies_short(&self, db: Arc<DB>) -> Result<Option<Vec<MovieShort>>, sqlx::Error> {
-        DB::q_get_movies_short(&db.conn, self.date, self.db_current_page, self.db_items_per_page).await
+    async fn q_get_sessions(&self, db: &dyn ScheduleRepository, movie_id: i32, now: DateTime<Utc>) -> DBResult<Option<Vec<Session>>> {
+        db.q_get_sessions_all(movie_id, self.date, now, self.badge).await
     }
 
-    fn get_menu_code(&self) -> MenuCode {
-        MenuCode::MovielistDefault
+    async fn q_get_movies_short(&self, db: &dyn ScheduleRepository, now: DateTime<Utc>) -> DBResult<Option<Vec<MovieShort>>> {
+        match &self.search {
+            Some(search) => {
+                db.q_search_movies(
+                    &search.text,
+                    self.date,
+                    now,
+                    self.badge,
+                    self.sort,
+                    &self.keyset,
+                    self.db_items_per_page,
+                )
+                .await
+            }
+            None => {
+                db.q_get_movies_short(self.date, now, self.badge, self.sort, &self.keyset, self.db_items_per_page)
+                    .await
+            }
+        }
     }
 
-    fn get_data_for_absence_answer(&self) -> (String, InlineKeyboardMarkup) {
-        let text = "Нету доступных фильмов для показа".to_string();
-        let keyboard = keyboard_ok_or_up(MenuCode::MovielistDefault);
+    fn get_data_for_absence_answer(&self, today: NaiveDate) -> (String, InlineKeyboardMarkup) {
+        let text = match &self.search {
+            // the button of the search may be pressed on another day
+            Some(search) if self.date == today => format!("По запросу «{}» сегодня ничего не нашлось", search.text),
+            Some(search) => format!(
+                "По запросу «{}» на {} ничего не нашлось",
+                search.text,
+                self.date
+                    .format_localized("%d.%m (%A)", chrono::Locale::ru_RU)
+                    .to_string()
+                    .to_lowercase()
+            ),
+            None => "Нету доступных фильмов для показа".to_string(),
+        };
+        let keyboard = keyboard_ok_or_up(self.up());
 
         (text, keyboard)
     }
@@ -101,63 +162,106 @@ impl Cbd for CallbackDataDefault {
         State::FromMovie { data: self }
     }
 
-    fn headline_text(&self) -> String {
-        let (curr_date, _) = datetime_utc3();
-
-        if curr_date == self.date {
+    fn headline_text(&self, today: NaiveDate) -> String {
+        let headline = if today == self.date {
             "Сегодня в прокате".to_string()
         } else {
             format!("{} в прокате", self.date.format_localized("%d.%m (%A)", chrono::Locale::ru_RU)).to_lowercase()
-        }
+        };
+        let headline = match &self.search {
+            Some(search) => format!("Поиск «{}» • {}", search.text, headline.to_lowercase()),
+            None => headline,
+        };
+
+        self.with_badge(headline)
+    }
+
+    fn movie_card_text(&self, movie: &Movie) -> String {
+        movie.description()
     }
 }
 
 #[async_trait]
 impl Cbd for CallbackDataCinema {
-    async fn go_prev(&self, bot: Bot, dialogue: MyDialogue, msg: Message, db: Arc<DB>) -> Res<()> {
-        if let Some(pinned_msg) = self.pinned_msg {
-            bot.delete_message(msg.chat.id, pinned_msg.id_msg).await?;
-        }
-        cb_handle_start_option(bot, dialogue, msg, db, ButtonOption::Cinemas as i32, self.date).await
-    }
-
-    // TODO
-    // check for bytes count (MAX LIMIT)
-    async fn show_sessions(&self, bot: Bot, q: CallbackQuery, sessions: Option<Vec<Session>>) -> Res<()> {
-        let text: String = match sessions {
-            Some(s) => s
-                .iter()
-                .map(|s| format!("{} - {} руб.", s.showtime.format("%H:%M"), s.price))
-                .collect::<Vec<String>>()
-                .join(" | "),
-            None => String::from("Нету доступных сеансов"),
-        };
+    fn schedule(&self) -> Schedule {
+        Schedule {
+            date: self.date,
+            badge: self.badge,
+            cinema_id: Some(self.cinema.id),
+        }
+    }
 
-        bot.answer_callback_query(q.id).text(text).show_alert(true).await?;
-        Ok(())
+    fn up(&self) -> Callback {
+        Callback::Cinemas {
+            date: self.date,
+            badge: self.badge,
+        }
     }
 
-    // TODO
-    // unwrap
-    async fn q_get_sessions(&mut self, conn: impl sqlx::PgExecutor<'_>) -> DBResult<Option<Vec<Session>>> {
-        DB::q_get_sessions_by_cinema(conn, self.pinned_msg.unwrap().db_id_movie, self.cinema.id, self.date).await
+    // the sessions are added to the movie card as buttons to buy tickets,
+    // the ones not fitting the buttons are listed in an alert, or all the sessions in a message if they don't fit it
+    async fn show_sessions(&self, bot: Bot, q: CallbackQuery, db: &dyn ScheduleRepository, movie_id: i32) -> Res<()> {
+        let sessions = self.q_get_sessions(db, movie_id, db.clock().now()).await?;
+
+        match (sessions, q.message) {
+            (Some(sessions), Some(msg)) => {
+                let rest: Vec<String> = sessions.iter().skip(MAX_TICKETS).map(session_text).collect();
+                let text = format!("Еще сеансы: {}", rest.join(", "));
+
+                if rest.is_empty() {
+                    bot.answer_callback_query(q.id).await?;
+                } else if text_len(&text) <= ALERT_MAX_LEN {
+                    bot.answer_callback_query(q.id).text(text).show_alert(true).await?;
+                } else {
+                    bot.answer_callback_query(q.id).await?;
+                    if let Some((text, keyboard)) = timetable(db, self.schedule(), movie_id, 1).await? {
+                        bot.send_message(msg.chat.id, text).reply_markup(keyboard).await?;
+                    }
+                }
+
+                let markup = msg.reply_markup().cloned().unwrap_or_default();
+                let keyboard = keyboard_with_tickets(&markup, &sessions);
+
+                // the same sessions are already shown
+                if keyboard != markup {
+                    bot.edit_message_reply_markup(msg.chat.id, msg.id).reply_markup(keyboard).await?;
+                }
+            }
+            _ => {
+                bot.answer_callback_query(q.id)
+                    .text("Нету доступных сеансов")
+                    .show_alert(true)
+                    .await?;
+            }
+        }
+        Ok(())
     }
 
-    async fn q_count_movies(&self, conn: impl sqlx::PgExecutor<'_>) -> Result<i64, sqlx::Error> {
-        DB::q_count_movies_by_cinema(conn, self.date, self.cinema.id).await
+    async fn q_get_sessions(&self, db: &dyn ScheduleRepository, movie_id: i32, now: DateTime<Utc>) -> DBResult<Option<Vec<Session>>> {
+        db.q_get_sessions_by_cinema(movie_id, self.cinema.id, self.date, now, self.badge)
+            .await
     }
 
-    async fn q_get_movies_short(&self, db: Arc<DB>) -> Result<Option<Vec<MovieShort>>, sqlx::Error> {
-        DB::q_get_movies_short_by_cinema(&db.conn, self.date, self.cinema.id, self.db_current_page, self.db_items_per_page).await
+    async fn q_count_movies(&self, db: &dyn ScheduleRepository, now: DateTime<Utc>) -> DBResult<i64> {
+        db.q_count_movies_by_cinema(self.date, now, self.cinema.id, self.badge).await
     }
 
-    fn get_menu_code(&self) -> MenuCode {
-        MenuCode::MovielistFromCinema
+    async fn q_get_movies_short(&self, db: &dyn ScheduleRepository, now: DateTime<Utc>) -> DBResult<Option<Vec<MovieShort>>> {
+        db.q_get_movies_short_by_cinema(
+            self.date,
+            now,
+            self.cinema.id,
+            self.badge,
+            self.sort,
+            &self.keyset,
+            self.db_items_per_page,
+        )
+        .await
     }
 
-    fn get_data_for_absence_answer(&self) -> (String, InlineKeyboardMarkup) {
+    fn get_data_for_absence_answer(&self, _today: NaiveDate) -> (String, InlineKeyboardMarkup) {
         let text = format!("В кинотеатре '{}' нету доступных фильмов для показа", self.cinema.name);
-        let keyboard = keyboard_ok_or_up(MenuCode::MovielistFromCinema);
+        let keyboard = keyboard_ok_or_up(self.up());
 
         (text, keyboard)
     }
@@ -166,74 +270,152 @@ impl Cbd for CallbackDataCinema {
         State::FromCinema { data: self }
     }
 
-    fn headline_text(&self) -> String {
-        let (curr_date, _) = datetime_utc3();
-
-        if curr_date == self.date {
+    fn headline_text(&self, today: NaiveDate) -> String {
+        let headline = if today == self.date {
             format!("Сегодня в кинотеатре {}", self.cinema.name)
         } else {
             let text_date = format!("{}", self.date.format_localized("%d.%m (%A)", chrono::Locale::ru_RU),).to_lowercase();
             format!("{} в кинотеатре {}", text_date, self.cinema.name)
+        };
+        let headline = self.with_badge(headline);
+
+        match self.cinema.location() {
+            Some(location) => format!("{}\n{}", headline, location),
+            None => headline,
+        }
+    }
+
+    // where the movie is shown
+    fn movie_card_text(&self, movie: &Movie) -> String {
+        match self.cinema.location() {
+            Some(location) => format!("{}\n📍 {}: {}", movie.description(), self.cinema.name, location),
+            None => movie.description(),
         }
     }
 }
 
 impl<T> CallbackData<T> {
+    // Сегодня в прокате • только субтитры
+    fn with_badge(&self, headline: String) -> String {
+        match self.badge {
+            Some(badge) => format!("{} • только {}", headline, badge.label()),
+            None => headline,
+        }
+    }
+
     // calculation of the number of movie pages
     pub fn set_total_pages(&mut self, db_movies_count: i64) {
-        let db_current_total_pages = (db_movies_count as f64 / self.db_items_per_page as f64).ceil() as i64;
+        self.db_total_pages = (db_movies_count as f64 / self.db_items_per_page as f64).ceil() as i64;
 
-        // if the number of pages has changed compared to the previous value (a movie has been added or removed),
-        // then reset the current page to 1 and set the new total_pages
-        if self.db_total_pages != db_current_total_pages {
+        // the list got shorter than the requested page (movies have been removed), so it's shown from the start
+        if self.db_current_page > self.db_total_pages {
             self.db_current_page = 1;
-            self.db_total_pages = db_current_total_pages;
+            self.keyset = Keyset::First;
         }
     }
 
-    pub fn set_current_page(&mut self, option: ButtonOption) -> bool {
-        if self.can_step(option) {
-            self.db_current_page += match option {
-                ButtonOption::Back => -1,
-                ButtonOption::Forward => 1,
-                _ => 0,
+    // the next page goes after the last movie of the shown one, the previous one before the first movie;
+    // the title of the movie completes the cursor, if the movie is gone the list is shown from the start
+    pub async fn set_page(&mut self, db: &dyn ScheduleRepository, page: Page) -> DBResult<()> {
+        self.db_current_page = 1;
+        self.keyset = Keyset::First;
+
+        let (number, key, movie_id, after) = match page {
+            Page::First => return Ok(()),
+            Page::After { number, key, movie_id } => (number, key, movie_id, true),
+            Page::Before { number, key, movie_id } => (number, key, movie_id, false),
+        };
+
+        if let Some(movie) = db.q_get_movie_by_id(movie_id).await? {
+            let cursor = MovieCursor {
+                key,
+                title: movie.title,
+                id: movie.id,
             };
-            return true;
+            self.db_current_page = number;
+            self.keyset = if after { Keyset::After(cursor) } else { Keyset::Before(cursor) };
         }
-        false
+        Ok(())
     }
 
-    fn can_step(&self, move_option: ButtonOption) -> bool {
-        !((self.db_current_page == 1 && move_option == ButtonOption::Back)
-            || (self.db_current_page == self.db_total_pages && move_option == ButtonOption::Forward))
+    // the orders to choose from, the found movies can also be sorted by the similarity to the text
+    pub fn sorts(&self) -> Vec<MovieSort> {
+        match self.search {
+            Some(_) => [MovieSort::Relevance].into_iter().chain(MovieSort::ALL).collect(),
+            None => MovieSort::ALL.to_vec(),
+        }
+    }
+}
+
+impl<T> CallbackData<T>
+where
+    CallbackData<T>: Cbd,
+{
+    // the list as it's put into the buttons
+    pub fn list(&self) -> MovieList {
+        MovieList {
+            schedule: self.schedule(),
+            sort: self.sort,
+            search: self.search.as_ref().map(|search| search.id),
+        }
     }
 }
 
 impl CallbackDataCinema {
-    pub fn new(date: NaiveDate, cinema: Cinema, id_msg: MessageId, pinned_msg: Option<CallbackPinnedMsg>, db_items_per_page: i64) -> Self {
+    pub fn new(
+        date: NaiveDate,
+        badge: Option<Badge>,
+        cinema: Cinema,
+        id_msg: MessageId,
+        pinned_msg: Option<CallbackPinnedMsg>,
+        db_items_per_page: i64,
+    ) -> Self {
         Self {
             date,
+            badge,
             id_msg,
             pinned_msg,
             db_current_page: 1,
             db_items_per_page,
             db_total_pages: 0,
+            sort: MovieSort::default(),
+            keyset: Keyset::First,
+            search: None,
             cinema,
         }
     }
 }
 impl CallbackDataDefault {
-    pub fn new(date: NaiveDate, id_msg: MessageId, pinned_msg: Option<CallbackPinnedMsg>, db_items_per_page: i64) -> Self {
+    pub fn new(
+        date: NaiveDate,
+        badge: Option<Badge>,
+        id_msg: MessageId,
+        pinned_msg: Option<CallbackPinnedMsg>,
+        db_items_per_page: i64,
+    ) -> Self {
         Self {
             date,
+            badge,
             id_msg,
             pinned_msg,
             db_current_page: 1,
             db_items_per_page,
             db_total_pages: 0,
+            sort: MovieSort::default(),
+            keyset: Keyset::First,
+            search: None,
             cinema: (),
         }
     }
+
+    // the movies found by the title, the most similar first
+    pub fn search(date: NaiveDate, search: Search, id_msg: MessageId, db_items_per_page: i64) -> Self {
+        Self {
+            sort: MovieSort::Relevance,
+            search: Some(search),
+            ..Self::new(date, None, id_msg, None, db_items_per_page)
+        }
+    }
 }
 
 impl CallbackPinnedMsg {
@@ -241,3 +423,173 @@ impl CallbackPinnedMsg {
         Self { id_msg, db_id_movie }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveTime, TimeZone};
+    use db::{clock::FixedClock, memory::MemoryRepository, tools::session_start, PriceKind};
+
+    fn session(id: i64, date: NaiveDate, time: &str, badges: Vec<Badge>) -> Session {
+        Session {
+            moskino_session_id: id,
+            cinema_name: String::new(),
+            starts_at: session_start(date, NaiveTime::parse_from_str(time, "%H:%M").unwrap()),
+            showdate: date,
+            price: 300,
+            price_kind: PriceKind::Paid,
+            badges,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cinema_movielist() {
+        let date = NaiveDate::from_ymd_opt(2023, 12, 23).unwrap();
+        // 14:00 in Moscow
+        let db = MemoryRepository::new(Arc::new(FixedClock(Utc.with_ymd_and_hms(2023, 12, 23, 11, 0, 0).unwrap())));
+
+        let cinema = Cinema {
+            name: "Сатурн".to_string(),
+            ..Default::default()
+        };
+        let cinema_id = db.insert_cinema(&cinema).await.unwrap();
+
+        let mut sessions = vec![];
+        for (id, title) in ["Олдбой", "Бешеные псы", "Следующая жертва"].iter().enumerate() {
+            let movie = Movie {
+                title: title.to_string(),
+                ..Default::default()
+            };
+            let movie_id = db.insert_movie(&movie).await.unwrap();
+            sessions.push((movie_id, session(id as i64, date, "21:30", vec![Badge::TwoD, Badge::Subtitles])));
+        }
+        // already over
+        sessions.push((1, session(10, date, "13:00", vec![Badge::ThreeD])));
+        db.replace_sessions(cinema_id, date, &sessions, true).await.unwrap();
+
+        let cinema = db.q_get_cinema_by_id(cinema_id).await.unwrap().unwrap();
+        let mut data = CallbackDataCinema::new(date, None, cinema, MessageId(1), None, 2);
+        let now = db.clock().now();
+
+        data.set_total_pages(data.q_count_movies(&db, now).await.unwrap());
+        assert_eq!(data.db_total_pages, 2);
+        let movies = data.q_get_movies_short(&db, now).await.unwrap().unwrap();
+        let titles: Vec<&str> = movies.iter().map(|movie| movie.title.as_str()).collect();
+        assert_eq!(titles, vec!["Бешеные псы", "Олдбой"]);
+
+        // the next page goes after the last movie, its title is taken from the db
+        let last = movies.last().unwrap();
+        let page = Page::After {
+            number: 2,
+            key: last.key,
+            movie_id: last.id,
+        };
+        data.set_page(&db, page).await.unwrap();
+        assert_eq!(data.keyset, Keyset::After(last.cursor()));
+        let movies = data.q_get_movies_short(&db, now).await.unwrap().unwrap();
+        assert_eq!(movies[0].title, "Следующая жертва");
+
+        let page = Page::Before {
+            number: 1,
+            key: movies[0].key,
+            movie_id: movies[0].id,
+        };
+        data.set_page(&db, page).await.unwrap();
+        assert_eq!(data.db_current_page, 1);
+        assert_eq!(data.q_get_movies_short(&db, now).await.unwrap().unwrap()[0].title, "Бешеные псы");
+
+        // the movie of the button is gone, so the list starts again
+        let page = Page::After {
+            number: 2,
+            key: 0,
+            movie_id: 999,
+        };
+        data.set_page(&db, page).await.unwrap();
+        assert_eq!((data.db_current_page, &data.keyset), (1, &Keyset::First));
+
+        // the page is beyond the shortened list
+        data.db_current_page = 3;
+        data.set_total_pages(2);
+        assert_eq!((data.db_current_page, data.db_total_pages), (1, 1));
+
+        let sessions = data.q_get_sessions(&db, 1, now).await.unwrap().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].showtime(), NaiveTime::from_hms_opt(21, 30, 0).unwrap());
+
+        // the 3D session is over, so no movie has one
+        data.badge = Some(Badge::ThreeD);
+        assert_eq!(data.q_count_movies(&db, now).await.unwrap(), 0);
+        assert!(data
+            .headline_text(db.clock().cinema_today())
+            .starts_with("Сегодня в кинотеатре Сатурн • только 3D"));
+
+        // the timetable of the cinema lists just its sessions
+        let berezka = Cinema {
+            name: "Березка".to_string(),
+            ..Default::default()
+        };
+        let berezka_id = db.insert_cinema(&berezka).await.unwrap();
+        db.replace_sessions(berezka_id, date, &[(1, session(20, date, "20:00", vec![Badge::TwoD]))], true)
+            .await
+            .unwrap();
+        data.badge = None;
+        let (text, _) = timetable(&db, data.schedule(), 1, 1).await.unwrap().unwrap();
+        assert_eq!(text, "Олдбой • сеансы сегодня\n\n📍 Сатурн\n21:30 · 300 ₽ · суб.");
+    }
+
+    #[tokio::test]
+    async fn test_search_movielist() {
+        let date = NaiveDate::from_ymd_opt(2023, 12, 23).unwrap();
+        let db = MemoryRepository::new(Arc::new(FixedClock(Utc.with_ymd_and_hms(2023, 12, 23, 11, 0, 0).unwrap())));
+
+        let cinema_id = db
+            .insert_cinema(&Cinema {
+                name: "Сатурн".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let mut sessions = vec![];
+        for (id, title) in ["Ёлки 10", "Олдбой", "Ёлки 5"].iter().enumerate() {
+            let movie = Movie {
+                title: title.to_string(),
+                ..Default::default()
+            };
+            let movie_id = db.insert_movie(&movie).await.unwrap();
+            sessions.push((movie_id, session(id as i64, date, "21:30", vec![Badge::TwoD])));
+        }
+        db.replace_sessions(cinema_id, date, &sessions, true).await.unwrap();
+
+        let search = Search {
+            id: db.insert_search("елки").await.unwrap(),
+            text: "елки".to_string(),
+        };
+        let mut data = CallbackDataDefault::search(date, search, MessageId(1), 10);
+        let now = db.clock().now();
+
+        assert_eq!(data.q_count_movies(&db, now).await.unwrap(), 2);
+        let movies = data.q_get_movies_short(&db, now).await.unwrap().unwrap();
+        let titles: Vec<&str> = movies.iter().map(|movie| movie.title.as_str()).collect();
+        assert_eq!(titles, vec!["Ёлки 10", "Ёлки 5"]);
+        assert_eq!(data.sorts()[0], MovieSort::Relevance);
+        assert_eq!(data.headline_text(date), "Поиск «елки» • сегодня в прокате");
+        // the buttons carry the id, the same text gets the same one
+        assert_eq!(data.list().search, Some(db.insert_search("елки").await.unwrap()));
+        assert_eq!(db.q_get_search(data.list().search.unwrap()).await.unwrap().as_deref(), Some("елки"));
+
+        data.search = Some(Search {
+            id: db.insert_search("терминатор").await.unwrap(),
+            text: "терминатор".to_string(),
+        });
+        assert!(data.q_get_movies_short(&db, now).await.unwrap().is_none());
+        assert_eq!(
+            data.get_data_for_absence_answer(date).0,
+            "По запросу «терминатор» сегодня ничего не нашлось"
+        );
+        // an old button of the search
+        assert_eq!(
+            data.get_data_for_absence_answer(date.succ_opt().unwrap()).0,
+            "По запросу «терминатор» на 23.12 (суббота) ничего не нашлось"
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use super::*;
+use chrono::{DateTime, Utc};
+use db::{Keyset, MovieSession, MovieSort, Session};
+use keyboard::*;
+use teloxide::types::{InlineQuery, InlineQueryResult, InlineQueryResultArticle, InputMessageContent, InputMessageContentText};
+use text::*;
+
+// how many movies are offered for an inline query
+const INLINE_MOVIES: i64 = 10;
+// how many movies of a cinema are put on its card
+const INLINE_CINEMA_MOVIES: usize = 30;
+// how many results are answered at once (telegram takes 50 at most),
+// the next ones are asked by telegram with the offset while the list is scrolled
+const INLINE_RESULTS: usize = 20;
+
+// what is offered for an inline query, its card is built only when it's on the answered page
+enum Offer {
+    Cinema(Cinema),
+    Movie(i32),
+}
+
+// "@bot <text>" in any chat: the cinemas and the movies matching the text,
+// the chosen one is sent as a card with today's sessions
+pub async fn inline_query_handler(bot: Bot, q: InlineQuery, db: Arc<dyn ScheduleRepository>) -> Res<()> {
+    let offset = q.offset.parse().unwrap_or(0);
+    let (results, next_offset) = inline_results(db.as_ref(), q.query.trim(), offset).await?;
+
+    // the schedule changes during the day, so telegram caches the answer just for a minute
+    bot.answer_inline_query(q.id, results)
+        .cache_time(60)
+        .next_offset(next_offset.map(|offset| offset.to_string()).unwrap_or_default())
+        .await?;
+    Ok(())
+}
+
+// the results starting from `offset` and the offset of the next ones if there are more
+pub async fn inline_results(db: &dyn ScheduleRepository, text: &str, offset: usize) -> Res<(Vec<InlineQueryResult>, Option<usize>)> {
+    let today = db.clock().cinema_today();
+    let now = db.clock().now();
+    let search = normalize(text);
+
+    // an empty query lists all the cinemas
+    let mut offers: Vec<Offer> = db
+        .q_get_cinemas()
+        .await?
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|cinema| normalize(&cinema.name).contains(&search))
+        .map(Offer::Cinema)
+        .collect();
+
+    if text.chars().count() >= 2 {
+        let movies = db
+            .q_search_movies(text, today, now, None, MovieSort::Relevance, &Keyset::First, INLINE_MOVIES)
+            .await?;
+        offers.extend(movies.unwrap_or_default().into_iter().map(|movie| Offer::Movie(movie.id)));
+    }
+
+    let next_offset = Some(offset + INLINE_RESULTS).filter(|&next| next < offers.len());
+
+    let mut results = vec![];
+    for offer in offers.into_iter().skip(offset).take(INLINE_RESULTS) {
+        match offer {
+            Offer::Cinema(cinema) => results.push(cinema_result(db, &cinema, today, now).await?),
+            Offer::Movie(movie_id) => {
+                if let Some(result) = movie_result(db, movie_id, today, now).await? {
+                    results.push(result);
+                }
+            }
+        }
+    }
+
+    Ok((results, next_offset))
+}
+
+async fn cinema_result(db: &dyn ScheduleRepository, cinema: &Cinema, today: NaiveDate, now: DateTime<Utc>) -> Res<InlineQueryResult> {
+    let sessions = db.q_get_sessions_of_cinema(cinema.id, today, now, None).await?;
+
+    // the movies by their first session
+    let mut movies: Vec<(i32, &str, Vec<Session>)> = vec![];
+    for MovieSession { movie_id, title, session } in sessions.iter().flatten() {
+        match movies.iter_mut().find(|(id, ..)| id == movie_id) {
+            Some((.., sessions)) => sessions.push(session.clone()),
+            None => movies.push((*movie_id, title, vec![session.clone()])),
+        }
+    }
+
+    let lines: Vec<String> = movies
+        .iter()
+        .take(INLINE_CINEMA_MOVIES)
+        .map(|(_, title, sessions)| format!("🎬 {}: {}", title, showtimes(sessions)))
+        .collect();
+
+    let mut text = format!("Сегодня в кинотеатре {}", cinema.name);
+    if let Some(location) = cinema.location() {
+        text.push_str(&format!("\n{}", location));
+    }
+    text.push_str("\n\n");
+    if lines.is_empty() {
+        text.push_str("Сеансов сегодня больше нет");
+    } else {
+        text.push_str(&lines.join("\n"));
+    }
+
+    let description = match lines.len() {
+        0 => "сеансов больше нет".to_string(),
+        n => format!("фильмов сегодня: {}", n),
+    };
+
+    let article =
+        InlineQueryResultArticle::new(format!("cinema:{}", cinema.id), &cinema.name, message_content(text)).description(description);
+    Ok(InlineQueryResult::Article(article))
+}
+
+async fn movie_result(db: &dyn ScheduleRepository, movie_id: i32, today: NaiveDate, now: DateTime<Utc>) -> Res<Option<InlineQueryResult>> {
+    let Some(movie) = db.q_get_movie_by_id(movie_id).await? else {
+        return Ok(None);
+    };
+
+    let mut sessions = db.q_get_sessions_all(movie_id, today, now, None).await?.unwrap_or_default();
+    sessions.sort_by(|a, b| (&a.cinema_name, a.starts_at).cmp(&(&b.cinema_name, b.starts_at)));
+
+    // 📍 Сатурн: 13:00, 21:30 (суб.)
+    let mut lines = vec![];
+    let mut from = 0;
+    for to in 1..=sessions.len() {
+        if to == sessions.len() || sessions[to].cinema_name != sessions[from].cinema_name {
+            lines.push(format!("📍 {}: {}", sessions[from].cinema_name, showtimes(&sessions[from..to])));
+            from = to;
+        }
+    }
+
+    let mut text = movie.description();
+    if lines.is_empty() {
+        text.push_str("\nСеансов сегодня больше нет");
+    } else {
+        text.push_str(&format!("\nСегодня:\n{}", lines.join("\n")));
+    }
+
+    let description = match lines.len() {
+        0 => "сеансов больше нет".to_string(),
+        n => format!("сегодня в кинотеатрах: {}", n),
+    };
+
+    let mut article =
+        InlineQueryResultArticle::new(format!("movie:{}", movie.id), &movie.title, message_content(text)).description(description);
+    if let Some(keyboard) = keyboard_movie_urls(movie.href_moskino.as_deref(), movie.href_kinopoisk.as_deref()) {
+        article = article.reply_markup(keyboard);
+    }
+    Ok(Some(InlineQueryResult::Article(article)))
+}
+
+// 13:00, 19:25, 21:30 (суб.)
+fn showtimes(sessions: &[Session]) -> String {
+    sessions
+        .iter()
+        .map(|session| {
+            let mut time = session.showtime().format("%H:%M").to_string();
+            // 2D is the usual format, so it is not shown
+            let badges: Vec<&str> = session
+                .badges
+                .iter()
+                .filter(|&&badge| badge != Badge::TwoD)
+                .map(|badge| badge.short_label())
+                .collect();
+            if !badges.is_empty() {
+                time.push_str(&format!(" ({})", badges.join(", ")));
+            }
+            time
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn message_content(text: String) -> InputMessageContent {
+    InputMessageContent::Text(InputMessageContentText::new(cut_text(&text, MESSAGE_MAX_LEN)))
+}
+
+// "Ёлки" is found by "елки"
+fn normalize(text: &str) -> String {
+    text.to_lowercase().replace('ё', "е")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveTime, TimeZone};
+    use db::{clock::FixedClock, memory::MemoryRepository, tools::session_start, Movie, PriceKind};
+
+    fn session(id: i64, date: NaiveDate, time: &str, badges: Vec<Badge>) -> Session {
+        Session {
+            moskino_session_id: id,
+            cinema_name: String::new(),
+            starts_at: session_start(date, NaiveTime::parse_from_str(time, "%H:%M").unwrap()),
+            showdate: date,
+            price: 300,
+            price_kind: PriceKind::Paid,
+            badges,
+        }
+    }
+
+    fn message_text(result: &InlineQueryResult) -> (&str, &str) {
+        match result {
+            InlineQueryResult::Article(article) => match &article.input_message_content {
+                InputMessageContent::Text(content) => (article.id.as_str(), content.message_text.as_str()),
+                _ => panic!("not a text message"),
+            },
+            _ => panic!("not an article"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inline_results() {
+        let date = NaiveDate::from_ymd_opt(2023, 12, 23).unwrap();
+        // 14:00 in Moscow
+        let db = MemoryRepository::new(Arc::new(FixedClock(Utc.with_ymd_and_hms(2023, 12, 23, 11, 0, 0).unwrap())));
+
+        let cinema_id = db
+            .insert_cinema(&Cinema {
+                name: "Сатурн".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let movie_id = db
+            .insert_movie(&Movie {
+                title: "Ёлки 10".to_string(),
+                href_moskino: Some("https://mos-kino.ru/film/elki-10/".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let sessions = vec![
+            (movie_id, session(1, date, "21:30", vec![Badge::TwoD, Badge::Subtitles])),
+            (movie_id, session(2, date, "17:00", vec![Badge::TwoD])),
+            // already over
+            (movie_id, session(3, date, "11:00", vec![Badge::TwoD])),
+        ];
+        db.replace_sessions(cinema_id, date, &sessions, true).await.unwrap();
+
+        let (results, next_offset) = inline_results(&db, "елки", 0).await.unwrap();
+        assert_eq!((results.len(), next_offset), (1, None));
+        let (id, text) = message_text(&results[0]);
+        assert_eq!(id, format!("movie:{}", movie_id));
+        assert!(text.starts_with("Ёлки 10\n"));
+        assert!(text.ends_with("Сегодня:\n📍 Сатурн: 17:00, 21:30 (суб.)"));
+
+        // all the cinemas for an empty query
+        let (results, _) = inline_results(&db, "", 0).await.unwrap();
+        let (id, text) = message_text(&results[0]);
+        assert_eq!(id, format!("cinema:{}", cinema_id));
+        assert_eq!(text, "Сегодня в кинотеатре Сатурн\n\n🎬 Ёлки 10: 17:00, 21:30 (суб.)");
+
+        // the rest is answered by the next offset
+        for n in 1..INLINE_RESULTS {
+            db.insert_cinema(&Cinema {
+                name: format!("Кинотеатр {}", n),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        }
+        let (results, next_offset) = inline_results(&db, "", 0).await.unwrap();
+        assert_eq!((results.len(), next_offset), (INLINE_RESULTS, None));
+        db.insert_cinema(&Cinema {
+            name: "Кинотеатр последний".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let (results, next_offset) = inline_results(&db, "", 0).await.unwrap();
+        assert_eq!((results.len(), next_offset), (INLINE_RESULTS, Some(INLINE_RESULTS)));
+        let (results, next_offset) = inline_results(&db, "", INLINE_RESULTS).await.unwrap();
+        assert_eq!((results.len(), next_offset), (1, None));
+    }
+}
@@ -1,158 +1,106 @@
 use super::*;
-use db::ArgDay;
+use callback::*;
+use db::{clock::Clock, ArgDay, Badge, MovieSort, Session};
+use text::*;
 use url::Url;
 
-pub const CD_DELIMETER: char = ':';
-
-// menu identifier
-#[derive(Debug, Default, PartialEq, Clone, Copy)]
-pub enum MenuCode {
-    #[default]
-    ChooseDay,
-    MainMenu,
-    Cinemas,
-    MovielistFromCinema,
-    MovielistDefault,
-    PinnedMovie,
+fn button(text: impl Into<String>, callback: Callback) -> InlineKeyboardButton {
+    InlineKeyboardButton::callback(text, callback.encode())
 }
 
-impl MenuCode {
-    pub fn check_complience(self, code: MenuCode) -> Res<()> {
-        if self != code {
-            Err(Box::new(io::Error::new(io::ErrorKind::Other, "Invalid MenuCode number")))
-        } else {
-            Ok(())
-        }
-    }
-}
+pub fn keyboard_day(clock: &dyn Clock) -> InlineKeyboardMarkup {
+    let today = ArgDay::get_date(ArgDay::Today, clock);
+    let tommorow = ArgDay::get_date(ArgDay::Tommorow, clock);
+    let aftertommorow = ArgDay::get_date(ArgDay::Aftertommorow, clock);
 
-impl TryFrom<i32> for MenuCode {
-    type Error = ();
-
-    fn try_from(v: i32) -> Result<Self, Self::Error> {
-        use MenuCode::*;
-        match v {
-            x if x == MainMenu as i32 => Ok(MainMenu),
-            x if x == Cinemas as i32 => Ok(Cinemas),
-            x if x == MovielistFromCinema as i32 => Ok(MovielistFromCinema),
-            x if x == MovielistDefault as i32 => Ok(MovielistDefault),
-            x if x == ChooseDay as i32 => Ok(ChooseDay),
-            x if x == PinnedMovie as i32 => Ok(PinnedMovie),
-            _ => Err(()),
-        }
-    }
-}
-
-// Идентификатор для нажатой управляющей кнопки (Назад, Вперед, Закрыть и т.д)
-#[derive(Debug, PartialEq, Clone, Copy)]
-pub enum ButtonOption {
-    // Выбрать день
-    Day = -100,
-    // Все фильмы
-    Movies,
-    // По кинотеатру
-    Cinemas,
-    // Назад
-    Back,
-    // Вперед
-    Forward,
-    // Назад (вверх, на предыдущее меню)
-    Up,
-    // Закрыть
-    Close,
-    //
-    Sessions,
-    // TODO
-    // Страницы (не исп.)
-    Pages,
-    // TODO
-    // (не исп.)
-    NotSetted,
-}
-
-impl TryFrom<i32> for ButtonOption {
-    type Error = ();
-
-    fn try_from(v: i32) -> Result<Self, Self::Error> {
-        use ButtonOption::*;
-        match v {
-            x if x == Movies as i32 => Ok(Movies),
-            x if x == Cinemas as i32 => Ok(Cinemas),
-            x if x == Back as i32 => Ok(Back),
-            x if x == Forward as i32 => Ok(Forward),
-            x if x == Up as i32 => Ok(Up),
-            x if x == Close as i32 => Ok(Close),
-            x if x == Pages as i32 => Ok(Pages),
-            x if x == Sessions as i32 => Ok(Sessions),
-            _ => Err(()),
-        }
-    }
-}
-
-pub fn keyboard_day() -> InlineKeyboardMarkup {
-    let today = ArgDay::get_date(ArgDay::Today);
-    let tommorow = ArgDay::get_date(ArgDay::Tommorow);
-    let aftertommorow = ArgDay::get_date(ArgDay::Aftertommorow);
-
-    let callback_data = format!("{}{}{}", MenuCode::ChooseDay as i32, CD_DELIMETER, today.format("%Y.%m.%d"));
-    let button_1 = InlineKeyboardButton::callback("Сегодня", callback_data);
+    let button_1 = button("Сегодня", Callback::Main { date: today, badge: None });
 
     let text = format!("{}", tommorow.format_localized("%A • %d.%m", chrono::Locale::ru_RU));
-    let callback_data = format!("{}{}{}", MenuCode::ChooseDay as i32, CD_DELIMETER, tommorow.format("%Y.%m.%d"));
-    let button_2 = InlineKeyboardButton::callback(text, callback_data);
+    let button_2 = button(
+        text,
+        Callback::Main {
+            date: tommorow,
+            badge: None,
+        },
+    );
 
     let text = format!("{}", aftertommorow.format_localized("%A • %d.%m", chrono::Locale::ru_RU));
-    let callback_data = format!("{}{}{}", MenuCode::ChooseDay as i32, CD_DELIMETER, aftertommorow.format("%Y.%m.%d"));
-    let button_3 = InlineKeyboardButton::callback(text, callback_data);
+    let button_3 = button(
+        text,
+        Callback::Main {
+            date: aftertommorow,
+            badge: None,
+        },
+    );
 
-    let callback_data = format!("{}{}{}", MenuCode::ChooseDay as i32, CD_DELIMETER, ButtonOption::Close as i32);
-    let button_4 = InlineKeyboardButton::callback("❌ Закрыть", callback_data);
+    let button_4 = button("❌ Закрыть", Callback::Close { also: None });
 
     InlineKeyboardMarkup::new(vec![vec![button_1], vec![button_2, button_3], vec![button_4]])
 }
 
-// Стартовое меню кнопок для выбора опции
+// Стартовое меню кнопок для выбора опции (✅ - выбранный фильтр сеансов)
 // | Все фильмы | По кинотеатру |
-// |       ❌ Закрыть           |
-pub fn keyboard_main() -> InlineKeyboardMarkup {
-    let callback_data = format!("{}{}{}", MenuCode::MainMenu as i32, CD_DELIMETER, ButtonOption::Movies as i32);
-    let button_1 = InlineKeyboardButton::callback("Все фильмы", callback_data);
+// | 2D | 3D | ✅ суб. | ориг. | ПК |
+// | ❌ Закрыть | Наверх ⬆ |
+pub fn keyboard_main(date: NaiveDate, badge: Option<Badge>) -> InlineKeyboardMarkup {
+    let list = MovieList::new(Schedule {
+        date,
+        badge,
+        cinema_id: None,
+    });
+    let button_1 = button("Все фильмы", Callback::Movies { list, page: Page::First });
 
-    let callback_data = format!("{}{}{}", MenuCode::MainMenu as i32, CD_DELIMETER, ButtonOption::Cinemas as i32);
-    let button_2 = InlineKeyboardButton::callback("По кинотеатру", callback_data);
+    let button_2 = button("По кинотеатру", Callback::Cinemas { date, badge });
 
-    let callback_data = format!("{}{}{}", MenuCode::MainMenu as i32, CD_DELIMETER, ButtonOption::Close as i32);
-    let button_3 = InlineKeyboardButton::callback("❌ Закрыть", callback_data);
+    let button_3 = button("❌ Закрыть", Callback::Close { also: None });
 
-    let callback_data = format!("{}{}{}", MenuCode::MainMenu as i32, CD_DELIMETER, ButtonOption::Up as i32);
-    let button_4 = InlineKeyboardButton::callback("️Наверх ⬆", callback_data);
+    let button_4 = button("️Наверх ⬆", Callback::Days);
+
+    // the same filter again turns it off
+    let filters: Vec<InlineKeyboardButton> = Badge::ALL
+        .iter()
+        .map(|&filter| {
+            if badge == Some(filter) {
+                button(format!("✅ {}", filter.short_label()), Callback::Main { date, badge: None })
+            } else {
+                button(filter.short_label(), Callback::Main { date, badge: Some(filter) })
+            }
+        })
+        .collect();
 
-    InlineKeyboardMarkup::new(vec![vec![button_1, button_2], vec![button_3, button_4]])
+    InlineKeyboardMarkup::new(vec![vec![button_1, button_2], filters, vec![button_3, button_4]])
 }
 
-// Меню кнопок с выбором кинотеатра
-// |  Березка   |  Вымпел  |
-// |   Искра    |  Космос  |
-// |         Сатурн        |
+// Меню кнопок с выбором кинотеатра (кружок - цвет ветки метро)
+// | ⚪ Березка | 🟢 Вымпел |
+// |  🟢 Искра  | 🟤 Космос |
+// |         🟠 Сатурн      |
 // | ❌ Закрыть | Наверх ⬆ |
-pub fn keyboard_cinemas(cinemas: Vec<Cinema>) -> InlineKeyboardMarkup {
+pub fn keyboard_cinemas(cinemas: Vec<Cinema>, date: NaiveDate, badge: Option<Badge>) -> InlineKeyboardMarkup {
     let buttons: Vec<InlineKeyboardButton> = cinemas
         .iter()
         .map(|cinema| {
-            let callback_data = format!("{}{}{}", MenuCode::Cinemas as i32, CD_DELIMETER, cinema.id);
-
-            InlineKeyboardButton::callback(cinema.name.to_owned(), callback_data)
+            let list = MovieList::new(Schedule {
+                date,
+                badge,
+                cinema_id: Some(cinema.id),
+            });
+
+            let text = match cinema.metro {
+                Some(_) => format!("{} {}", cinema.metro_marker(), cinema.name),
+                None => cinema.name.to_owned(),
+            };
+
+            button(text, Callback::Movies { list, page: Page::First })
         })
         .collect();
 
     // Группируем кнопки кинотеатров
     let mut keyboard: Vec<Vec<InlineKeyboardButton>> = buttons.chunks(2).map(|buttons_row| buttons_row.to_vec()).collect();
 
-    let callback_data = format!("{}{}{}", MenuCode::Cinemas as i32, CD_DELIMETER, ButtonOption::Close as i32);
-    let button_1 = InlineKeyboardButton::callback("❌ Закрыть", callback_data);
+    let button_1 = button("❌ Закрыть", Callback::Close { also: None });
 
-    let callback_data = format!("{}{}{}", MenuCode::Cinemas as i32, CD_DELIMETER, ButtonOption::Up as i32);
-    let button_2 = InlineKeyboardButton::callback("️Наверх ⬆", callback_data);
+    let button_2 = button("️Наверх ⬆", Callback::Main { date, badge });
 
     // Добавляем кнопки управления
     keyboard.push(vec![button_1, button_2]);
@@ -164,36 +112,73 @@ pub fn keyboard_cinemas(cinemas: Vec<Cinema>) -> InlineKeyboardMarkup {
 // | Форест Гамп (1994) |
 // | Шоу Трумана (1998) |
 // | Леон (1994)        |
+// | ✅ А-Я | время | сеансы | новинки |  (и 🔎 - по запросу, при поиске)
 // | ⬅️ | ➡ | 1 из 9 | ⬆ |
-pub fn keyboard_movielist(movies: Vec<MovieShort>, menu_code: MenuCode, page: i64, total_pages: i64) -> InlineKeyboardMarkup {
+// the arrows of the first and the last pages do nothing
+pub fn keyboard_movielist(
+    movies: Vec<MovieShort>,
+    list: &MovieList,
+    page: i64,
+    total_pages: i64,
+    sorts: &[MovieSort],
+    up: Callback,
+) -> InlineKeyboardMarkup {
     let buttons: Vec<InlineKeyboardButton> = movies
         .iter()
-        // .flat_map(|m| {
         .map(|m| {
-            let callback_data_1 = format!("{}{}{}", menu_code as i32, CD_DELIMETER, m.id);
-            // let callback_data_2 = format!("{}{}{}", menu_code as i32, CD_DELIMETER, m.id);
-            //  🗓
-            // vec![
-            InlineKeyboardButton::callback(&m.title, callback_data_1)
-            // InlineKeyboardButton::callback(" 🗓", callback_data_2),
-            // ]
+            let movie = Callback::Movie {
+                schedule: list.schedule,
+                movie_id: m.id,
+            };
+            button(&m.title, movie)
         })
         .collect();
 
     // Группируем кнопки кинотеатров
     let mut keyboard: Vec<Vec<InlineKeyboardButton>> = buttons.chunks(1).map(|buttons_row| buttons_row.to_vec()).collect();
 
-    let callback_data = format!("{}{}{}", menu_code as i32, CD_DELIMETER, ButtonOption::Back as i32);
-    let button_1 = InlineKeyboardButton::callback("⬅️", callback_data);
-
-    let callback_data = format!("{}{}{}", menu_code as i32, CD_DELIMETER, ButtonOption::Forward as i32);
-    let button_2 = InlineKeyboardButton::callback("➡️", callback_data);
-
-    let callback_data = format!("{}{}{}", menu_code as i32, CD_DELIMETER, ButtonOption::Pages as i32);
-    let button_3 = InlineKeyboardButton::callback(format!("{} из {}", page, total_pages), callback_data);
-
-    let callback_data = format!("{}{}{}", menu_code as i32, CD_DELIMETER, ButtonOption::Up as i32);
-    let button_4 = InlineKeyboardButton::callback("️⬆", callback_data);
+    let sorts: Vec<InlineKeyboardButton> = sorts
+        .iter()
+        .map(|&order| {
+            if list.sort == order {
+                button(format!("✅ {}", order.short_label()), Callback::Noop)
+            } else {
+                let list = MovieList { sort: order, ..*list };
+                button(order.short_label(), Callback::Movies { list, page: Page::First })
+            }
+        })
+        .collect();
+    keyboard.push(sorts);
+
+    let back = match movies.first() {
+        Some(first) if page > 1 => Callback::Movies {
+            list: *list,
+            page: Page::Before {
+                number: page - 1,
+                key: first.key,
+                movie_id: first.id,
+            },
+        },
+        _ => Callback::Noop,
+    };
+    let button_1 = button("⬅️", back);
+
+    let forward = match movies.last() {
+        Some(last) if page < total_pages => Callback::Movies {
+            list: *list,
+            page: Page::After {
+                number: page + 1,
+                key: last.key,
+                movie_id: last.id,
+            },
+        },
+        _ => Callback::Noop,
+    };
+    let button_2 = button("➡️", forward);
+
+    let button_3 = button(format!("{} из {}", page, total_pages), Callback::Noop);
+
+    let button_4 = button("️⬆", up);
 
     // Добавляем кнопки управления
     keyboard.push(vec![button_1, button_2, button_3, button_4]);
@@ -201,51 +186,278 @@ pub fn keyboard_movielist(movies: Vec<MovieShort>, menu_code: MenuCode, page: i6
     InlineKeyboardMarkup::new(keyboard)
 }
 
-// Меню кнопок для варианта 'Все фильмы', когда нету доступных фильмов в прокате
-// | Ок 😔 |
-// pub fn keyboard_ok(menu_code: MenuCode) -> InlineKeyboardMarkup {
-//     let callback_data = format!("{}{}{}", menu_code as i32, CD_DELIMETER, ButtonOption::Close as i32);
-//     let button_1 = InlineKeyboardButton::callback("😔 Ок", callback_data);
-//     InlineKeyboardMarkup::new(vec![vec![button_1]])
-// }
-
 // Меню кнопок для варианта 'По кинотеатру', когда у выбранного кинотеатра нету доступных фильмов в прокате
 // или когда нету доступных кинотеатров для показа
 // | Ок 😔 | ⬆️ Наверх |
-pub fn keyboard_ok_or_up(menu_code: MenuCode) -> InlineKeyboardMarkup {
-    let callback_data = format!("{}{}{}", menu_code as i32, CD_DELIMETER, ButtonOption::Close as i32);
-    let button_1 = InlineKeyboardButton::callback("😔 Ок", callback_data);
-
-    let callback_data = format!("{}{}{}", menu_code as i32, CD_DELIMETER, ButtonOption::Up as i32);
-    let button_2 = InlineKeyboardButton::callback("️Наверх ⬆", callback_data);
+pub fn keyboard_ok_or_up(up: Callback) -> InlineKeyboardMarkup {
+    let button_1 = button("😔 Ок", Callback::Close { also: None });
+    let button_2 = button("️Наверх ⬆", up);
     InlineKeyboardMarkup::new(vec![vec![button_1, button_2]])
 }
 
-// TODO
-// Ок | [Кинопоиск (link)] | Москино 🗓
-pub fn keybord_movie_links(href_mk: Option<&str>, href_kp: Option<&str>, menu_code: MenuCode) -> InlineKeyboardMarkup {
+// Ок | 🗓 | Москино | Кинопоиск
+// "Ок" closes the card and the list it was opened from
+pub fn keybord_movie_links(
+    href_mk: Option<&str>,
+    href_kp: Option<&str>,
+    schedule: Schedule,
+    movie_id: i32,
+    id_list_msg: MessageId,
+) -> InlineKeyboardMarkup {
     let mut keyboard: Vec<InlineKeyboardButton> = vec![];
 
-    let callback_data = format!("{}{}{}", menu_code as i32, CD_DELIMETER, ButtonOption::Close as i32);
-    let button_1 = InlineKeyboardButton::callback("🌝 Ок", callback_data);
+    let button_1 = button("🌝 Ок", Callback::Close { also: Some(id_list_msg) });
     keyboard.push(button_1);
 
-    let callback_data = format!("{}{}{}", menu_code as i32, CD_DELIMETER, ButtonOption::Sessions as i32);
-    let button_2 = InlineKeyboardButton::callback("🗓", callback_data);
+    let button_2 = button("🗓", Callback::Sessions { schedule, movie_id });
     keyboard.push(button_2);
 
-    if let Some(mk) = href_mk {
-        let parsed = Url::parse(mk).unwrap();
-        let button_3 = InlineKeyboardButton::url("Москино", parsed);
-        keyboard.push(button_3);
+    keyboard.extend(movie_url_buttons(href_mk, href_kp));
+
+    let keyboard: Vec<Vec<InlineKeyboardButton>> = keyboard.chunks(2).map(|b| b.to_vec()).collect();
+    InlineKeyboardMarkup::new(keyboard)
+}
+
+// | Москино | Кинопоиск |
+// only links: a message sent via inline mode is not in the chat with the bot, so its buttons would have nothing to edit
+pub fn keyboard_movie_urls(href_mk: Option<&str>, href_kp: Option<&str>) -> Option<InlineKeyboardMarkup> {
+    let buttons = movie_url_buttons(href_mk, href_kp);
+
+    if buttons.is_empty() {
+        None
+    } else {
+        Some(InlineKeyboardMarkup::new(vec![buttons]))
     }
+}
 
-    if let Some(kp) = href_kp {
-        let parsed = Url::parse(kp).unwrap();
-        let button_4 = InlineKeyboardButton::url("Кинопоиск", parsed);
-        keyboard.push(button_4);
+fn movie_url_buttons(href_mk: Option<&str>, href_kp: Option<&str>) -> Vec<InlineKeyboardButton> {
+    [("Москино", href_mk), ("Кинопоиск", href_kp)]
+        .into_iter()
+        .filter_map(|(text, href)| {
+            href.and_then(|href| Url::parse(href).ok())
+                .map(|url| InlineKeyboardButton::url(text, url))
+        })
+        .collect()
+}
+
+// Сеансы фильма по всем кинотеатрам, по страницам
+// | ⬅️ | 1 из 3 | ➡️ |
+// | 🌝 Ок |
+pub fn keyboard_timetable(schedule: Schedule, movie_id: i32, page: i64, total_pages: i64) -> InlineKeyboardMarkup {
+    let mut keyboard = vec![];
+
+    if total_pages > 1 {
+        let timetable = |page: i64| Callback::Timetable { schedule, movie_id, page };
+        let back = if page > 1 { timetable(page - 1) } else { Callback::Noop };
+        let forward = if page < total_pages { timetable(page + 1) } else { Callback::Noop };
+
+        keyboard.push(vec![
+            button("⬅️", back),
+            button(format!("{} из {}", page, total_pages), Callback::Noop),
+            button("➡️", forward),
+        ]);
     }
+    keyboard.push(vec![button("🌝 Ок", Callback::Close { also: None })]);
+
+    InlineKeyboardMarkup::new(keyboard)
+}
+
+// moskino sells tickets in a widget of the schedule page (ticketManager.richSession(id) of a session),
+// a session has no page of its own, so a ticket opens the schedule of the date
+pub const DEFAULT_TICKET_URL: &str = "https://mos-kino.ru/schedule/?date={date}";
+
+// $MOSKINO_BOT_TICKET_URL if it makes valid urls, the default one otherwise (checked once at startup)
+pub fn ticket_url_template(template: Option<String>) -> String {
+    let Some(template) = template else {
+        return DEFAULT_TICKET_URL.to_string();
+    };
+
+    match fill_ticket_url(&template, 1, NaiveDate::from_ymd_opt(2023, 12, 23).unwrap()) {
+        Some(_) => template,
+        None => {
+            error!("MOSKINO_BOT_TICKET_URL is not a url: {}, {} is used", template, DEFAULT_TICKET_URL);
+            DEFAULT_TICKET_URL.to_string()
+        }
+    }
+}
+
+fn fill_ticket_url(template: &str, moskino_session_id: i64, showdate: NaiveDate) -> Option<Url> {
+    // the session stored before the moskino ids
+    let template = if moskino_session_id == 0 && template.contains("{id}") {
+        DEFAULT_TICKET_URL
+    } else {
+        template
+    };
+    let url = template
+        .replace("{id}", &moskino_session_id.to_string())
+        .replace("{date}", &showdate.format("%Y-%m-%d").to_string());
+
+    Url::parse(&url).ok()
+}
+
+// ссылка на покупку билета на сеанс
+pub fn ticket_url(session: &Session) -> Option<Url> {
+    fill_ticket_url(&TICKET_URL, session.moskino_session_id, session.showdate)
+}
+
+const TICKET_MARK: &str = "🎟";
+// 10 rows of tickets still leave the card readable on a phone screen,
+// the rest of the sessions are listed in an alert (or in a message if they don't fit it)
+pub const MAX_TICKETS: usize = 30;
+
+// Кнопки покупки билетов добавляются под карточку фильма (прежние заменяются)
+// | 🌝 Ок | 🗓 |
+// | Москино | Кинопоиск |
+// | 🎟 13:00 · 200 ₽ | 🎟 19:25 · 300 ₽ | 🎟 21:30 · 250 ₽ · суб. |
+pub fn keyboard_with_tickets(markup: &InlineKeyboardMarkup, sessions: &[Session]) -> InlineKeyboardMarkup {
+    let mut keyboard: Vec<Vec<InlineKeyboardButton>> = markup
+        .inline_keyboard
+        .iter()
+        .filter(|row| !row.iter().any(|button| button.text.starts_with(TICKET_MARK)))
+        .cloned()
+        .collect();
+
+    let buttons: Vec<InlineKeyboardButton> = sessions
+        .iter()
+        .take(MAX_TICKETS)
+        .filter_map(|session| {
+            let text = format!("{} {}", TICKET_MARK, session_text(session));
+            ticket_url(session).map(|url| InlineKeyboardButton::url(text, url))
+        })
+        .collect();
+
+    keyboard.extend(buttons.chunks(3).map(|row| row.to_vec()));
 
-    let keyboard: Vec<Vec<InlineKeyboardButton>> = keyboard.chunks(2).map(|b| b.to_vec()).collect();
     InlineKeyboardMarkup::new(keyboard)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveTime;
+    use db::{tools::session_start, PriceKind};
+
+    fn session(id: i64, time: &str) -> Session {
+        let showdate = NaiveDate::from_ymd_opt(2023, 12, 23).unwrap();
+
+        Session {
+            moskino_session_id: id,
+            cinema_name: "Сатурн".to_string(),
+            starts_at: session_start(showdate, NaiveTime::parse_from_str(time, "%H:%M").unwrap()),
+            showdate,
+            price: 200,
+            price_kind: PriceKind::Paid,
+            badges: vec![Badge::TwoD],
+        }
+    }
+
+    #[test]
+    fn test_keyboard_with_tickets() {
+        let schedule = Schedule {
+            date: NaiveDate::from_ymd_opt(2023, 12, 23).unwrap(),
+            badge: None,
+            cinema_id: Some(100),
+        };
+        let card = keybord_movie_links(None, None, schedule, 1, MessageId(1));
+        let sessions = vec![session(96619320, "13:00"), session(96546614, "19:25")];
+
+        let keyboard = keyboard_with_tickets(&card, &sessions);
+        assert_eq!(keyboard.inline_keyboard.len(), card.inline_keyboard.len() + 1);
+
+        let ticket = &keyboard.inline_keyboard.last().unwrap()[0];
+        assert_eq!(ticket.text, "🎟 13:00 · 200 ₽");
+        assert_eq!(
+            ticket.kind,
+            teloxide::types::InlineKeyboardButtonKind::Url(Url::parse("https://mos-kino.ru/schedule/?date=2023-12-23").unwrap())
+        );
+
+        let mut sessions = sessions;
+        sessions[1].badges.push(Badge::Subtitles);
+        let keyboard = keyboard_with_tickets(&card, &sessions);
+        assert_eq!(keyboard.inline_keyboard.last().unwrap()[1].text, "🎟 19:25 · 200 ₽ · суб.");
+
+        sessions[0].price_kind = PriceKind::Free;
+        let keyboard = keyboard_with_tickets(&card, &sessions);
+        assert_eq!(keyboard.inline_keyboard.last().unwrap()[0].text, "🎟 13:00 · бесплатно");
+
+        // the tickets are replaced, not added again
+        assert_eq!(keyboard_with_tickets(&keyboard, &sessions), keyboard);
+
+        // the rest of the sessions don't fit the buttons
+        let sessions: Vec<Session> = (0..40).map(|id| session(id, "13:00")).collect();
+        let keyboard = keyboard_with_tickets(&card, &sessions);
+        let tickets = keyboard
+            .inline_keyboard
+            .iter()
+            .flatten()
+            .filter(|button| button.text.starts_with(TICKET_MARK));
+        assert_eq!(tickets.count(), MAX_TICKETS);
+    }
+
+    #[test]
+    fn test_ticket_url() {
+        let date = NaiveDate::from_ymd_opt(2023, 12, 23).unwrap();
+        let template = ticket_url_template(Some("https://tickets.example/{date}/{id}".to_string()));
+
+        assert_eq!(
+            fill_ticket_url(&template, 96619320, date).unwrap().as_str(),
+            "https://tickets.example/2023-12-23/96619320"
+        );
+        // no id, the schedule of the date
+        assert_eq!(
+            fill_ticket_url(&template, 0, date).unwrap().as_str(),
+            "https://mos-kino.ru/schedule/?date=2023-12-23"
+        );
+
+        assert_eq!(ticket_url_template(Some("mos-kino.ru/{id}".to_string())), DEFAULT_TICKET_URL);
+        assert_eq!(ticket_url_template(None), DEFAULT_TICKET_URL);
+    }
+
+    #[test]
+    fn test_keyboard_movielist() {
+        let list = MovieList::new(Schedule {
+            date: NaiveDate::from_ymd_opt(2023, 12, 23).unwrap(),
+            badge: None,
+            cinema_id: None,
+        });
+        let movies = vec![
+            MovieShort {
+                id: 12,
+                title: "Бешеные псы".to_string(),
+                key: 0,
+            },
+            MovieShort {
+                id: 10,
+                title: "Олдбой".to_string(),
+                key: 0,
+            },
+        ];
+
+        let keyboard = keyboard_movielist(movies, &list, 1, 3, &MovieSort::ALL, Callback::Days);
+        let callback = |row: usize, column: usize| match &keyboard.inline_keyboard[row][column].kind {
+            teloxide::types::InlineKeyboardButtonKind::CallbackData(data) => Callback::decode(data).unwrap(),
+            _ => panic!("not a callback button"),
+        };
+
+        assert_eq!(
+            callback(0, 0),
+            Callback::Movie {
+                schedule: list.schedule,
+                movie_id: 12
+            }
+        );
+        // the current order and the back arrow of the first page
+        assert_eq!((callback(2, 0), callback(3, 0)), (Callback::Noop, Callback::Noop));
+        assert_eq!(
+            callback(3, 1),
+            Callback::Movies {
+                list,
+                page: Page::After {
+                    number: 2,
+                    key: 0,
+                    movie_id: 10
+                }
+            }
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use super::*;
+use chrono::Duration;
+use db::repository::DialogueRepository;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{future::Future, marker::PhantomData, pin::Pin};
+use teloxide::dispatching::dialogue::{ErasedStorage, InMemStorage, Storage};
+
+type StorageFuture<T> = Pin<Box<dyn Future<Output = Result<T, Errr>> + Send>>;
+
+// how often the stale dialogues are deleted
+const PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+// where the dialogues are kept, by $MOSKINO_BOT_DIALOGUE_STORAGE:
+// - postgres: moskino.dialogues, the open menus survive a restart of the bot
+// - memory: lost on restart
+pub fn dialogue_storage(kind: &str, db: Arc<dyn DialogueRepository>, ttl: Duration) -> Res<Arc<ErasedStorage<State>>> {
+    match kind {
+        "postgres" => {
+            tokio::spawn(purge_stale_dialogues(db.clone(), ttl));
+            Ok(Arc::new(DbStorage::<State>::new(db, ttl)))
+        }
+        "memory" => Ok(InMemStorage::<State>::new().erase()),
+        _ => Err(Box::new(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Unknown dialogue storage: {}", kind),
+        ))),
+    }
+}
+
+async fn purge_stale_dialogues(db: Arc<dyn DialogueRepository>, ttl: Duration) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match db.delete_stale_dialogues(ttl).await {
+            Ok(0) => {}
+            Ok(deleted) => info!("Dialogues: {} stale deleted", deleted),
+            Err(e) => error!("Dialogues: couldn't delete stale: {}", e),
+        }
+    }
+}
+
+// the dialogue of a chat as json, a dialogue not updated for `ttl` is treated as absent
+pub struct DbStorage<D> {
+    db: Arc<dyn DialogueRepository>,
+    ttl: Duration,
+    dialogue: PhantomData<fn() -> D>,
+}
+
+impl<D> DbStorage<D> {
+    pub fn new(db: Arc<dyn DialogueRepository>, ttl: Duration) -> Self {
+        Self {
+            db,
+            ttl,
+            dialogue: PhantomData,
+        }
+    }
+}
+
+impl<D> Storage<D> for DbStorage<D>
+where
+    D: Serialize + DeserializeOwned + Send + 'static,
+{
+    type Error = Errr;
+
+    fn remove_dialogue(self: Arc<Self>, chat_id: ChatId) -> StorageFuture<()>
+    where
+        D: Send + 'static,
+    {
+        Box::pin(async move { Ok(self.db.delete_dialogue(chat_id.0).await?) })
+    }
+
+    fn update_dialogue(self: Arc<Self>, chat_id: ChatId, dialogue: D) -> StorageFuture<()>
+    where
+        D: Send + 'static,
+    {
+        Box::pin(async move {
+            let state = serde_json::to_value(&dialogue)?;
+            Ok(self.db.upsert_dialogue(chat_id.0, &state).await?)
+        })
+    }
+
+    fn get_dialogue(self: Arc<Self>, chat_id: ChatId) -> StorageFuture<Option<D>> {
+        Box::pin(async move {
+            let Some(state) = self.db.q_get_dialogue(chat_id.0, self.ttl).await? else {
+                return Ok(None);
+            };
+
+            // e.g. stored by the previous version of the bot, the user starts over
+            match serde_json::from_value(state) {
+                Ok(dialogue) => Ok(Some(dialogue)),
+                Err(e) => {
+                    log::warn!("Dialogue of {} is dropped: {}", chat_id, e);
+                    Ok(None)
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use db::{clock::FixedClock, memory::MemoryRepository};
+
+    #[test]
+    fn test_state_json() {
+        let cinema = Cinema {
+            id: 100,
+            name: "Сатурн".to_string(),
+            ..Default::default()
+        };
+        let date = NaiveDate::from_ymd_opt(2023, 12, 23).unwrap();
+        let mut data = CallbackDataCinema::new(date, Some(Badge::Subtitles), cinema, MessageId(1), None, 4);
+        data.pinned_msg = Some(CallbackPinnedMsg::new(MessageId(2), 101));
+
+        let state = serde_json::to_value(State::FromCinema { data }).unwrap();
+        let Ok(State::FromCinema { data }) = serde_json::from_value(state) else {
+            panic!("not the cinema movie list");
+        };
+        assert_eq!((data.cinema.name.as_str(), data.badge), ("Сатурн", Some(Badge::Subtitles)));
+        assert_eq!(data.pinned_msg.map(|pinned| pinned.db_id_movie), Some(101));
+
+        // a state of an unknown format is not restored
+        assert!(serde_json::from_value::<State>(serde_json::json!({ "Removed": {} })).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_db_storage() {
+        let db = Arc::new(MemoryRepository::new(Arc::new(FixedClock(
+            Utc.with_ymd_and_hms(2023, 12, 23, 11, 0, 0).unwrap(),
+        ))));
+        let storage = Arc::new(DbStorage::<State>::new(db.clone(), Duration::hours(48)));
+        let date = NaiveDate::from_ymd_opt(2023, 12, 23).unwrap();
+
+        storage
+            .clone()
+            .update_dialogue(ChatId(1), State::Cinemas { date, badge: None })
+            .await
+            .unwrap();
+        let state = storage.clone().get_dialogue(ChatId(1)).await.unwrap();
+        assert!(matches!(state, Some(State::Cinemas { badge: None, .. })));
+
+        // stored by another version of the bot
+        db.upsert_dialogue(2, &serde_json::json!({ "Removed": {} })).await.unwrap();
+        assert!(storage.clone().get_dialogue(ChatId(2)).await.unwrap().is_none());
+
+        storage.clone().remove_dialogue(ChatId(1)).await.unwrap();
+        assert!(storage.get_dialogue(ChatId(1)).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,193 @@
+use super::*;
+use db::Session;
+
+// telegram limits of a message and of a callback alert, in UTF-16 code units (an emoji may take two)
+pub const MESSAGE_MAX_LEN: usize = 4096;
+pub const ALERT_MAX_LEN: usize = 200;
+
+pub fn text_len(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+// the text cut to `max_len` with "…" at the end
+pub fn cut_text(text: &str, max_len: usize) -> String {
+    if text_len(text) <= max_len {
+        return text.to_string();
+    }
+
+    let mut cut = String::new();
+    let mut len = '…'.len_utf16();
+    for c in text.chars() {
+        len += c.len_utf16();
+        if len > max_len {
+            break;
+        }
+        cut.push(c);
+    }
+    cut.push('…');
+    cut
+}
+
+// 21:30 · 250 ₽ · суб.
+pub fn session_text(session: &Session) -> String {
+    let mut text = session.showtime().format("%H:%M").to_string();
+    if let Some(price) = session.price_text() {
+        text.push_str(&format!(" · {}", price));
+    }
+    // 2D is the usual format, so it is not shown
+    for badge in session.badges.iter().filter(|&&badge| badge != Badge::TwoD) {
+        text.push_str(&format!(" · {}", badge.short_label()));
+    }
+    text
+}
+
+// Олдбой • сеансы сегодня
+// Только сеансы: субтитры
+pub fn timetable_headline(title: &str, date: NaiveDate, today: NaiveDate, badge: Option<Badge>) -> String {
+    let headline = if date == today {
+        format!("{} • сеансы сегодня", title)
+    } else {
+        let text_date = date
+            .format_localized("%d.%m (%A)", chrono::Locale::ru_RU)
+            .to_string()
+            .to_lowercase();
+        format!("{} • сеансы {}", title, text_date)
+    };
+
+    match badge {
+        Some(badge) => format!("{}\nТолько сеансы: {}", headline, badge.label()),
+        None => headline,
+    }
+}
+
+// the sessions grouped by cinema, split into pages of at most `max_len`:
+// a cinema goes to the next page unless it fits the current one, a cinema longer than a page is split by sessions,
+// the headline with the cinema name is cut if even it doesn't fit a page
+//
+// Олдбой • сеансы сегодня
+//
+// 📍 Березка
+// 20:00 · по приглашениям
+//
+// 📍 Сатурн
+// 13:00 · 200 ₽
+// 21:30 · 250 ₽ · суб.
+pub fn timetable_pages(headline: &str, sessions: &[Session], max_len: usize) -> Vec<String> {
+    let mut sessions = sessions.to_vec();
+    sessions.sort_by(|a, b| (&a.cinema_name, a.starts_at).cmp(&(&b.cinema_name, b.starts_at)));
+
+    let mut pages = vec![];
+    let mut page = headline.to_string();
+
+    for group in by_cinema(&sessions) {
+        let cinema = format!("\n\n📍 {}", group[0].cinema_name);
+        let lines: Vec<String> = group.iter().map(|session| format!("\n{}", session_text(session))).collect();
+        let block = format!("{}{}", cinema, lines.concat());
+
+        if text_len(&page) + text_len(&block) <= max_len {
+            page.push_str(&block);
+            continue;
+        }
+        if text_len(headline) + text_len(&block) <= max_len {
+            pages.push(std::mem::replace(&mut page, format!("{}{}", headline, block)));
+            continue;
+        }
+
+        // the cinema is continued on the next page under its name again
+        let mut with_cinema = false;
+        for line in lines {
+            if !with_cinema {
+                if page != headline && text_len(&page) + text_len(&cinema) + text_len(&line) > max_len {
+                    pages.push(std::mem::replace(&mut page, headline.to_string()));
+                }
+                page.push_str(&cinema);
+                with_cinema = true;
+            } else if text_len(&page) + text_len(&line) > max_len {
+                pages.push(std::mem::replace(&mut page, format!("{}{}", headline, cinema)));
+            }
+            page.push_str(&cut_text(&line, max_len.saturating_sub(text_len(headline) + text_len(&cinema))));
+        }
+    }
+    pages.push(page);
+
+    pages.into_iter().map(|page| cut_text(&page, max_len)).collect()
+}
+
+// the sessions sorted by cinema, split into the cinemas
+fn by_cinema(sessions: &[Session]) -> Vec<&[Session]> {
+    let mut groups = vec![];
+    let mut from = 0;
+    for to in 1..=sessions.len() {
+        if to == sessions.len() || sessions[to].cinema_name != sessions[from].cinema_name {
+            groups.push(&sessions[from..to]);
+            from = to;
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveTime;
+    use db::{tools::session_start, PriceKind};
+
+    fn session(id: i64, cinema: &str, time: &str) -> Session {
+        let showdate = NaiveDate::from_ymd_opt(2023, 12, 23).unwrap();
+
+        Session {
+            moskino_session_id: id,
+            cinema_name: cinema.to_string(),
+            starts_at: session_start(showdate, NaiveTime::parse_from_str(time, "%H:%M").unwrap()),
+            showdate,
+            price: 200,
+            price_kind: PriceKind::Paid,
+            badges: vec![Badge::TwoD],
+        }
+    }
+
+    #[test]
+    fn test_cut_text() {
+        assert_eq!(cut_text("Олдбой", 6), "Олдбой");
+        assert_eq!(cut_text("Олдбой", 5), "Олдб…");
+        // 🎟 takes two units
+        assert_eq!(cut_text("🎟🎟🎟", 5), "🎟🎟…");
+    }
+
+    #[test]
+    fn test_timetable_pages() {
+        let sessions = vec![
+            session(1, "Сатурн", "21:30"),
+            session(2, "Березка", "20:00"),
+            session(3, "Сатурн", "13:00"),
+        ];
+
+        let pages = timetable_pages("Олдбой", &sessions, MESSAGE_MAX_LEN);
+        assert_eq!(
+            pages,
+            vec!["Олдбой\n\n📍 Березка\n20:00 · 200 ₽\n\n📍 Сатурн\n13:00 · 200 ₽\n21:30 · 200 ₽"]
+        );
+
+        // a cinema is moved to the next page as a whole
+        let pages = timetable_pages("Олдбой", &sessions, 50);
+        assert_eq!(
+            pages,
+            vec![
+                "Олдбой\n\n📍 Березка\n20:00 · 200 ₽",
+                "Олдбой\n\n📍 Сатурн\n13:00 · 200 ₽\n21:30 · 200 ₽"
+            ]
+        );
+
+        // or split by sessions if it's longer than a page
+        let pages = timetable_pages("Олдбой", &sessions, 35);
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[2], "Олдбой\n\n📍 Сатурн\n21:30 · 200 ₽");
+        assert!(pages.iter().all(|page| text_len(page) <= 35));
+
+        // the title and the cinema don't fit a page at all, they are cut
+        let pages = timetable_pages("Олдбой", &sessions, 10);
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[0], "Олдбой\n\n…");
+        assert!(pages.iter().all(|page| text_len(page) <= 10));
+    }
+}
@@ -0,0 +1,15 @@
+-- every run of web-parser (times are Moscow local, like moskino.users.last_active)
+CREATE TABLE IF NOT EXISTS moskino.scrape_runs (
+    run_id SERIAL PRIMARY KEY,
+    showdate DATE NOT NULL,
+    started_at timestamp NOT NULL,
+    finished_at timestamp,
+    -- running | ok | partial (some items were skipped) | failed (the run was aborted)
+    status VARCHAR(20) NOT NULL,
+    cinemas INTEGER NOT NULL DEFAULT 0,
+    movies INTEGER NOT NULL DEFAULT 0,
+    sessions INTEGER NOT NULL DEFAULT 0,
+    errors INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS scrape_runs_showdate_idx ON moskino.scrape_runs (showdate, finished_at);
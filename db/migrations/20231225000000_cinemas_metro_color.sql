@@ -0,0 +1,2 @@
+-- colour of the metro line near the cinema, e.g. #EF8532
+ALTER TABLE moskino.cinemas ADD COLUMN IF NOT EXISTS metro_color VARCHAR(7);
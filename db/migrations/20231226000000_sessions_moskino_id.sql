@@ -0,0 +1,7 @@
+-- sessions are identified by their id on moskino (tickets are sold by it)
+-- instead of (cinema, movie, date, time, price): a rescheduled session keeps its id.
+-- the stored sessions have no id yet, they are kept (the bot goes on showing them)
+-- and get it from web-parser when their date is scraped again (DB::replace_sessions),
+-- the old key stays for them until it goes away with showtime (20231229000000_timestamptz)
+ALTER TABLE moskino.sessions ADD COLUMN moskino_session_id BIGINT;
+ALTER TABLE moskino.sessions ADD CONSTRAINT sessions_moskino_session_id_key UNIQUE (moskino_session_id);
@@ -0,0 +1,2 @@
+-- format of the session: 2d, 3d, subtitles, original (language), pushkin (card)
+ALTER TABLE moskino.sessions ADD COLUMN IF NOT EXISTS badges VARCHAR(20)[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,11 @@
+-- paid, free, invitation or unknown (the price is 0 unless paid)
+ALTER TABLE moskino.sessions ADD COLUMN IF NOT EXISTS price_kind VARCHAR(20) NOT NULL DEFAULT 'paid';
+
+-- 0 used to be stored for any price that couldn't be parsed
+UPDATE moskino.sessions SET price_kind = 'unknown' WHERE price = 0;
+
+-- the cinema day starts at 06:00 (db::tools::CINEMA_DAY_START):
+-- the sessions after midnight belong to the previous date of the schedule and go after 23:59
+CREATE OR REPLACE FUNCTION moskino.cinema_time(showtime TIME) RETURNS TIME
+    LANGUAGE SQL IMMUTABLE
+    AS $$ SELECT showtime - INTERVAL '6 hours' $$;
@@ -0,0 +1,21 @@
+-- the moments are stored as timestamptz (they used to be Moscow local)
+
+-- a session after midnight belongs to the previous date of the schedule (the cinema day starts at 06:00)
+ALTER TABLE moskino.sessions ADD COLUMN IF NOT EXISTS starts_at TIMESTAMPTZ;
+
+UPDATE moskino.sessions
+SET starts_at = (showdate + showtime + CASE WHEN showtime < TIME '06:00' THEN INTERVAL '1 day' ELSE INTERVAL '0' END)
+    AT TIME ZONE 'Europe/Moscow';
+
+ALTER TABLE moskino.sessions ALTER COLUMN starts_at SET NOT NULL;
+ALTER TABLE moskino.sessions DROP COLUMN showtime;
+
+-- the upcoming sessions are just the ones with starts_at >= now
+DROP FUNCTION IF EXISTS moskino.cinema_time(TIME);
+
+ALTER TABLE moskino.scrape_runs
+    ALTER COLUMN started_at TYPE TIMESTAMPTZ USING started_at AT TIME ZONE 'Europe/Moscow',
+    ALTER COLUMN finished_at TYPE TIMESTAMPTZ USING finished_at AT TIME ZONE 'Europe/Moscow';
+
+ALTER TABLE moskino.users
+    ALTER COLUMN last_active TYPE TIMESTAMPTZ USING last_active AT TIME ZONE 'Europe/Moscow';
@@ -0,0 +1,18 @@
+-- movies are searched by the title (the bot: /search or just a text)
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- ё is typed as е more often than not
+CREATE OR REPLACE FUNCTION moskino.search_text(text TEXT) RETURNS TEXT
+    LANGUAGE SQL IMMUTABLE
+    AS $$ SELECT translate(lower(text), 'ё', 'е') $$;
+
+-- the title has the words of the query (russian stemming: жертвы ~ жертва)
+-- or a part similar to the query (typos: олдбй ~ олдбой)
+CREATE OR REPLACE FUNCTION moskino.title_matches(title TEXT, query TEXT) RETURNS BOOLEAN
+    LANGUAGE SQL IMMUTABLE
+    AS $$
+    SELECT to_tsvector('russian', moskino.search_text(title)) @@ plainto_tsquery('russian', moskino.search_text(query))
+        OR moskino.search_text(query) <% moskino.search_text(title)
+    $$;
+
+CREATE INDEX IF NOT EXISTS movies_title_trgm_idx ON moskino.movies USING gin (moskino.search_text(title) gin_trgm_ops);
@@ -0,0 +1,9 @@
+-- the state of the bot menus of a chat (serialized State), so they survive a restart of the bot
+CREATE TABLE IF NOT EXISTS moskino.dialogues (
+    chat_id BIGINT PRIMARY KEY,
+    state JSONB NOT NULL,
+    updated_at timestamptz NOT NULL
+);
+
+-- stale dialogues are purged by the update time
+CREATE INDEX IF NOT EXISTS dialogues_updated_at_idx ON moskino.dialogues (updated_at);
@@ -0,0 +1,6 @@
+-- when web-parser last looked the movie up on the sites (moskino, kinopoisk),
+-- a movie without a kinopoisk match is enriched all the same and isn't looked up on every run
+ALTER TABLE moskino.movies ADD COLUMN IF NOT EXISTS enriched_at TIMESTAMPTZ;
+
+-- the movies found on both sites were treated as enriched
+UPDATE moskino.movies SET enriched_at = now() WHERE href_moskino IS NOT NULL AND href_kinopoisk IS NOT NULL;
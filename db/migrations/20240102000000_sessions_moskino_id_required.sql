@@ -0,0 +1,7 @@
+-- a session is stored with its moskino id from now on. The check is NOT VALID, so the sessions
+-- stored before the ids (20231226000000_sessions_moskino_id) aren't checked: web-parser gives them the id
+-- when their date is scraped again. Once none is left without it, the column can be made NOT NULL:
+--   ALTER TABLE moskino.sessions VALIDATE CONSTRAINT sessions_moskino_session_id_required;
+--   ALTER TABLE moskino.sessions ALTER COLUMN moskino_session_id SET NOT NULL;
+ALTER TABLE moskino.sessions
+    ADD CONSTRAINT sessions_moskino_session_id_required CHECK (moskino_session_id IS NOT NULL) NOT VALID;
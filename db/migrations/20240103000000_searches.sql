@@ -0,0 +1,7 @@
+-- the texts searched by the title (/search), the buttons of the found movies carry the id,
+-- as the text itself may not fit the callback data
+CREATE TABLE IF NOT EXISTS moskino.searches (
+    search_id SERIAL PRIMARY KEY,
+    text TEXT NOT NULL UNIQUE,
+    created_at timestamptz NOT NULL
+);
@@ -0,0 +1,58 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use std::fmt::Debug;
+
+use super::tools::cinema_date;
+
+// the schedule is in Moscow time
+pub const MOSCOW: Tz = chrono_tz::Europe::Moscow;
+
+// where the current moment comes from, a fixed one is injected in tests
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    fn now_msk(&self) -> DateTime<Tz> {
+        self.now().with_timezone(&MOSCOW)
+    }
+
+    // after midnight 'today' is still the previous cinema day
+    fn cinema_today(&self) -> NaiveDate {
+        let now = self.now_msk();
+        cinema_date(now.date_naive(), now.time())
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_cinema_today() {
+        // 00:30 in Moscow is 21:30 UTC of the previous day
+        let clock = FixedClock(Utc.with_ymd_and_hms(2023, 12, 23, 21, 30, 0).unwrap());
+        assert_eq!(clock.now_msk().date_naive(), NaiveDate::from_ymd_opt(2023, 12, 24).unwrap());
+        assert_eq!(clock.cinema_today(), NaiveDate::from_ymd_opt(2023, 12, 23).unwrap());
+
+        let clock = FixedClock(Utc.with_ymd_and_hms(2023, 12, 24, 3, 0, 0).unwrap());
+        assert_eq!(clock.cinema_today(), NaiveDate::from_ymd_opt(2023, 12, 24).unwrap());
+    }
+}
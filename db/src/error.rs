@@ -0,0 +1,85 @@
+use std::{error, fmt};
+
+// what went wrong with a query, so the callers don't have to dig into sqlx errors
+#[derive(Debug)]
+pub enum Error {
+    // the row is absent
+    NotFound,
+    // a unique constraint is violated, e.g. the name of the cinema is already stored
+    Duplicate { constraint: Option<String> },
+    // the row breaks a foreign key or a check, or the transaction clashed with another one
+    Conflict { constraint: Option<String>, reason: String },
+    // postgres can't be reached right now, it makes sense to try again later
+    Unavailable(sqlx::Error),
+    Other(sqlx::Error),
+}
+
+impl Error {
+    pub fn is_unavailable(&self) -> bool {
+        matches!(self, Self::Unavailable(_))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "no rows returned"),
+            Self::Duplicate { constraint } => write!(f, "duplicate ({})", constraint.as_deref().unwrap_or("unique")),
+            Self::Conflict { reason, .. } => write!(f, "conflict: {}", reason),
+            Self::Unavailable(e) => write!(f, "database is unavailable: {}", e),
+            Self::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Unavailable(e) | Self::Other(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => Self::NotFound,
+            sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::WorkerCrashed => {
+                Self::Unavailable(e)
+            }
+            sqlx::Error::Database(ref db_e) => {
+                let constraint = db_e.constraint().map(str::to_string);
+
+                // https://www.postgresql.org/docs/current/errcodes-appendix.html
+                match db_e.code().as_deref() {
+                    Some("23505") => Self::Duplicate { constraint },
+                    // foreign key, not null, check, exclusion; serialization failure, deadlock
+                    Some("23503" | "23502" | "23514" | "23P01" | "40001" | "40P01") => Self::Conflict {
+                        constraint,
+                        reason: db_e.message().to_string(),
+                    },
+                    // connection exceptions, too many connections, shutdown
+                    Some(code) if code.starts_with("08") || code == "53300" || code.starts_with("57P") => Self::Unavailable(e),
+                    _ => Self::Other(e),
+                }
+            }
+            e => Self::Other(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_sqlx() {
+        assert!(matches!(Error::from(sqlx::Error::RowNotFound), Error::NotFound));
+        assert!(Error::from(sqlx::Error::PoolTimedOut).is_unavailable());
+        assert!(matches!(
+            Error::from(sqlx::Error::ColumnNotFound("title".to_string())),
+            Error::Other(_)
+        ));
+    }
+}
@@ -1,14 +1,22 @@
-use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use clock::{Clock, SystemClock, MOSCOW};
+use serde::{Deserialize, Serialize};
 use sqlx::{
     postgres::PgPool,
-    postgres::{PgPoolOptions, PgQueryResult},
-    Pool, Postgres, Row,
+    postgres::{PgHasArrayType, PgPoolOptions, PgQueryResult, PgTypeInfo},
+    Pool, Postgres,
 };
-use tools::{datetime_utc3, time_determine};
+use std::sync::Arc;
 
+pub mod clock;
+pub mod error;
+pub mod memory;
+pub mod repository;
 pub mod tools;
 
-pub type DBResult<T> = Result<T, sqlx::Error>;
+pub use error::Error;
+
+pub type DBResult<T> = Result<T, Error>;
 
 #[derive(Debug, Clone)]
 pub enum ArgDay {
@@ -18,20 +26,24 @@ pub enum ArgDay {
 }
 
 impl ArgDay {
-    pub fn get_date(arg: ArgDay) -> NaiveDate {
+    pub fn get_date(arg: ArgDay, clock: &dyn Clock) -> NaiveDate {
         use ArgDay::*;
 
+        // after midnight 'today' is still the previous cinema day
         match arg {
-            Today => (Utc::now() + Duration::hours(3)).date_naive(),
-            Tommorow => (Utc::now() + Duration::hours(3) + Duration::days(1)).date_naive(),
-            Aftertommorow => (Utc::now() + Duration::hours(3) + Duration::days(2)).date_naive(),
+            Today => clock.cinema_today(),
+            Tommorow => clock.cinema_today() + Duration::days(1),
+            Aftertommorow => clock.cinema_today() + Duration::days(2),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Movie {
+    // 0 until stored
+    pub id: i32,
     pub title: String,
+    // 0 if unknown
     pub year: Option<i32>,
     pub genre: Option<String>,
     pub director: Option<String>,
@@ -39,70 +51,364 @@ pub struct Movie {
     pub href_moskino: Option<String>,
     pub href_kinopoisk: Option<String>,
     pub country: Option<String>,
+    // minutes
     pub duration: Option<i32>,
+    // age rating, e.g. 16 (16+)
     pub age: Option<i32>,
     pub tagline: Option<String>,
 }
 
 impl Movie {
+    // the movie card:
+    //
+    // По щучьему велению
+    // «Емеля и волшебная щука»
+    //
+    // Год: 2023
+    // Страна: Россия
+    // ...
+    //
+    // описание
     pub fn description(&self) -> String {
+        let details = [
+            self.year.filter(|&year| year > 0).map(|year| format!("Год: {}", year)),
+            self.country.as_ref().map(|country| format!("Страна: {}", country)),
+            self.genre.as_ref().map(|genre| format!("Жанр: {}", genre)),
+            self.director.as_ref().map(|director| format!("Режиссер: {}", director)),
+            self.runtime().map(|runtime| format!("Длительность: {}", runtime)),
+            self.age.map(|age| format!("Возраст: {}+", age)),
+        ];
+
         format!(
-            "{}{}{}{}{}{}\n",
+            "{}{}\n{}\n{}\n",
             self.title,
-            self.year.map_or("".to_string(), |year| format!("\n\nГод: {}", year)),
-            self.genre.as_ref().map_or("".to_string(), |genre| format!("\nЖанр: {}", genre)),
-            self.director
-                .as_ref()
-                .map_or("".to_string(), |director| format!("\nРежиссер: {}", director)),
-            "\n",
+            self.tagline.as_ref().map_or("".to_string(), |tagline| format!("\n«{}»", tagline)),
+            details.iter().flatten().map(|detail| format!("\n{}", detail)).collect::<String>(),
             self.description
                 .as_ref()
                 .map_or("".to_string(), |description| format!("\n{}", description)),
         )
     }
+
+    // 1 ч 55 мин
+    pub fn runtime(&self) -> Option<String> {
+        match self.duration? {
+            duration if duration <= 0 => None,
+            duration if duration < 60 => Some(format!("{} мин", duration)),
+            duration if duration % 60 == 0 => Some(format!("{} ч", duration / 60)),
+            duration => Some(format!("{} ч {} мин", duration / 60, duration % 60)),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct MovieShort {
     pub id: i32,
     pub title: String,
+    // the value the list is sorted by (see MovieSort)
+    pub key: i64,
 }
 
-#[derive(Debug, Clone)]
+impl MovieShort {
+    pub fn cursor(&self) -> MovieCursor {
+        MovieCursor {
+            key: self.key,
+            title: self.title.clone(),
+            id: self.id,
+        }
+    }
+}
+
+// order of the movie list, ties are broken by the title and the id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "varchar", rename_all = "lowercase")]
+pub enum MovieSort {
+    #[default]
+    Title,
+    // the earliest upcoming session first
+    Showtime,
+    // the most sessions first
+    Sessions,
+    // the newest release first
+    Release,
+    // the most similar to the search text first
+    Relevance,
+}
+
+impl MovieSort {
+    pub const ALL: [MovieSort; 4] = [MovieSort::Title, MovieSort::Showtime, MovieSort::Sessions, MovieSort::Release];
+
+    pub fn short_label(&self) -> &'static str {
+        match self {
+            MovieSort::Title => "А-Я",
+            MovieSort::Showtime => "время",
+            MovieSort::Sessions => "сеансы",
+            MovieSort::Release => "новинки",
+            MovieSort::Relevance => "🔎",
+        }
+    }
+}
+
+// position of a movie in the sorted list (fields are compared in this order)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct MovieCursor {
+    pub key: i64,
+    pub title: String,
+    pub id: i32,
+}
+
+// which page of the sorted list is requested: the movies go right after or before the cursor,
+// so the pages don't shift while the list is being updated
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum Keyset {
+    #[default]
+    First,
+    After(MovieCursor),
+    Before(MovieCursor),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Cinema {
     pub id: i32,
     pub name: String,
+    pub address: Option<String>,
+    pub metro: Option<String>,
+    // colour of the metro line, e.g. #EF8532
+    pub metro_color: Option<String>,
 }
 
+// circles telegram can show instead of the metro line colour
+// (matched by the colours of the moscow metro lines)
+const METRO_MARKERS: [(&str, (i32, i32, i32)); 10] = [
+    ("🔴", (239, 22, 30)),
+    ("🟢", (45, 190, 44)),
+    ("🟢", (153, 204, 0)),
+    ("🔵", (0, 120, 190)),
+    ("🟤", (141, 91, 45)),
+    ("🟠", (237, 145, 33)),
+    ("🟣", (128, 0, 128)),
+    ("🟡", (255, 215, 2)),
+    ("⚪", (170, 170, 170)),
+    ("⚫", (40, 40, 40)),
+];
+
 impl Cinema {
-    pub fn new(id: i32, name: String) -> Self {
-        Self { id, name }
+    // the circle closest to the colour of the metro line
+    pub fn metro_marker(&self) -> &'static str {
+        let rgb = self
+            .metro_color
+            .as_deref()
+            .and_then(|color| color.strip_prefix('#'))
+            // #abc is #aabbcc
+            .map(|hex| match hex.len() {
+                3 => hex.chars().flat_map(|c| [c, c]).collect(),
+                _ => hex.to_string(),
+            })
+            .filter(|hex| hex.len() == 6)
+            .and_then(|hex| u32::from_str_radix(&hex, 16).ok())
+            .map(|rgb| ((rgb >> 16) as i32, ((rgb >> 8) & 0xFF) as i32, (rgb & 0xFF) as i32));
+
+        match rgb {
+            Some((r, g, b)) => {
+                METRO_MARKERS
+                    .iter()
+                    .min_by_key(|(_, (mr, mg, mb))| (r - mr).pow(2) + (g - mg).pow(2) + (b - mb).pow(2))
+                    .unwrap()
+                    .0
+            }
+            None => "Ⓜ️",
+        }
+    }
+
+    // Снежная ул., д. 18 • 🟠 Свиблово
+    pub fn location(&self) -> Option<String> {
+        let metro = self.metro.as_ref().map(|metro| format!("{} {}", self.metro_marker(), metro));
+
+        match (&self.address, metro) {
+            (Some(address), Some(metro)) => Some(format!("{} • {}", address, metro)),
+            (Some(address), None) => Some(address.to_string()),
+            (None, metro) => metro,
+        }
     }
 }
 
-#[derive(Debug)]
+// format of a session, marked by a badge on the schedule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "varchar")]
+pub enum Badge {
+    #[sqlx(rename = "2d")]
+    TwoD,
+    #[sqlx(rename = "3d")]
+    ThreeD,
+    #[sqlx(rename = "subtitles")]
+    Subtitles,
+    // на языке оригинала
+    #[sqlx(rename = "original")]
+    Original,
+    // можно оплатить Пушкинской картой
+    #[sqlx(rename = "pushkin")]
+    Pushkin,
+}
+
+impl PgHasArrayType for Badge {
+    fn array_type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("_varchar")
+    }
+}
+
+impl Badge {
+    pub const ALL: [Badge; 5] = [Badge::TwoD, Badge::ThreeD, Badge::Subtitles, Badge::Original, Badge::Pushkin];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Badge::TwoD => "2D",
+            Badge::ThreeD => "3D",
+            Badge::Subtitles => "субтитры",
+            Badge::Original => "на языке оригинала",
+            Badge::Pushkin => "Пушкинская карта",
+        }
+    }
+
+    pub fn short_label(&self) -> &'static str {
+        match self {
+            Badge::TwoD => "2D",
+            Badge::ThreeD => "3D",
+            Badge::Subtitles => "суб.",
+            Badge::Original => "ориг.",
+            Badge::Pushkin => "ПК",
+        }
+    }
+}
+
+// how a session is paid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, sqlx::Type)]
+#[sqlx(type_name = "varchar", rename_all = "lowercase")]
+pub enum PriceKind {
+    #[default]
+    Paid,
+    Free,
+    // по приглашениям
+    Invitation,
+    // the price couldn't be parsed
+    Unknown,
+}
+
+#[derive(Debug, Clone)]
 pub struct Session {
+    // id of the session on moskino, 0 if unknown (stored before the ids were)
+    pub moskino_session_id: i64,
     pub cinema_name: String,
-    pub showtime: NaiveTime,
+    pub starts_at: DateTime<Utc>,
+    // the date of the schedule (the cinema day), a session after midnight starts the next calendar date
     pub showdate: NaiveDate,
+    // 0 unless the session is paid
     pub price: i32,
+    pub price_kind: PriceKind,
+    pub badges: Vec<Badge>,
+}
+
+impl Session {
+    // moscow time
+    pub fn showtime(&self) -> NaiveTime {
+        self.starts_at.with_timezone(&MOSCOW).time()
+    }
+
+    // the session can still be attended
+    pub fn is_upcoming(&self, clock: &dyn Clock) -> bool {
+        self.starts_at >= clock.now()
+    }
+
+    // 250 ₽, бесплатно, по приглашению
+    pub fn price_text(&self) -> Option<String> {
+        match self.price_kind {
+            PriceKind::Paid => Some(format!("{} ₽", self.price)),
+            PriceKind::Free => Some("бесплатно".to_string()),
+            PriceKind::Invitation => Some("по приглашению".to_string()),
+            PriceKind::Unknown => None,
+        }
+    }
+}
+
+// a session with its movie, e.g. in the schedule of a cinema
+#[derive(Debug, Clone)]
+pub struct MovieSession {
+    pub movie_id: i32,
+    pub title: String,
+    pub session: Session,
+}
+
+// outcome of DB::replace_sessions
+#[derive(Debug, Default, PartialEq)]
+pub struct SessionsReplaced {
+    pub inserted: usize,
+    // already stored before
+    pub existing: usize,
+    pub removed: u64,
+}
+
+// state of a web-parser run
+#[derive(Debug, Clone, Copy, PartialEq, sqlx::Type)]
+#[sqlx(type_name = "varchar", rename_all = "lowercase")]
+pub enum RunStatus {
+    Running,
+    Ok,
+    // some items were skipped
+    Partial,
+    // the run was aborted
+    Failed,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScrapeRun {
+    pub id: i32,
+    pub showdate: NaiveDate,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub status: RunStatus,
+    pub cinemas: i32,
+    pub movies: i32,
+    pub sessions: i32,
+    pub errors: i32,
+}
+
+impl ScrapeRun {
+    // the schedule wasn't updated for too long (or the run hangs)
+    pub fn is_stale(&self, now: DateTime<Utc>, max_age: Duration) -> bool {
+        let updated_at = self.finished_at.unwrap_or(self.started_at);
+
+        now - updated_at > max_age
+    }
 }
 
+#[derive(Clone)]
 pub struct DB {
     pub conn: Pool<Postgres>,
+    // the current moment for 'upcoming' sessions, 'today' and the run times
+    pub clock: Arc<dyn Clock>,
 }
 
 impl DB {
     pub async fn pool(url: &str, max_conn: u32) -> DBResult<Self> {
         let conn = PgPoolOptions::new().max_connections(max_conn).connect(url).await?;
 
-        Ok(Self { conn })
+        Ok(Self::from_pool(conn))
+    }
+
+    // e.g. the pool of a test database
+    pub fn from_pool(conn: Pool<Postgres>) -> Self {
+        Self {
+            conn,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    // e.g. a fixed moment in tests
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..self }
     }
 
     pub async fn new(url: &str) -> DBResult<Self> {
-        Ok(Self {
-            conn: PgPool::connect(url).await?,
-        })
+        Ok(Self::from_pool(PgPool::connect(url).await?))
     }
 
     pub async fn q_get_sessions_by_cinema(
@@ -110,17 +416,20 @@ impl DB {
         movie_id: i32,
         cinema_id: i32,
         date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
     ) -> DBResult<Option<Vec<Session>>> {
-        let time = time_determine(date);
-
         let sessions: Vec<Session> = sqlx::query_as!(
             Session,
             r#"
             SELECT
+                COALESCE(s.moskino_session_id, 0) as "moskino_session_id!",
                 c.name as cinema_name,
-                s.showtime as showtime,
+                s.starts_at,
                 s.showdate as showdate,
-                s.price as price
+                s.price as price,
+                s.price_kind as "price_kind: PriceKind",
+                s.badges as "badges: Vec<Badge>"
             FROM
                 moskino.sessions s
             JOIN
@@ -134,14 +443,17 @@ impl DB {
             AND
             	s.showdate = $3
             AND
-                s.showtime >= $4
+                s.starts_at >= $4
+            AND
+                ($5::varchar IS NULL OR $5 = ANY(s.badges))
             ORDER BY
-                cinema_name, showtime;
+                cinema_name, s.starts_at;
             "#,
             movie_id,
             cinema_id,
             date,
-            time
+            now,
+            badge as Option<Badge>
         )
         .fetch_all(conn)
         .await?;
@@ -153,18 +465,92 @@ impl DB {
         }
     }
 
-    // выборка сеансов по фильму по всем кинотеатрам за определенную дату
-    pub async fn q_get_sessions_all(conn: impl sqlx::PgExecutor<'_>, movie_id: i32, date: NaiveDate) -> DBResult<Option<Vec<Session>>> {
-        let time = time_determine(date);
+    // выборка всех сеансов кинотеатра за определенную дату, с фильмами (одним запросом)
+    pub async fn q_get_sessions_of_cinema(
+        conn: impl sqlx::PgExecutor<'_>,
+        cinema_id: i32,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+    ) -> DBResult<Option<Vec<MovieSession>>> {
+        let sessions: Vec<MovieSession> = sqlx::query!(
+            r#"
+            SELECT
+                m.movie_id,
+                m.title,
+                COALESCE(s.moskino_session_id, 0) as "moskino_session_id!",
+                c.name as cinema_name,
+                s.starts_at,
+                s.showdate as showdate,
+                s.price as price,
+                s.price_kind as "price_kind: PriceKind",
+                s.badges as "badges: Vec<Badge>"
+            FROM
+                moskino.sessions s
+            JOIN
+                moskino.movies m ON s.movie_id = m.movie_id
+            JOIN
+                moskino.cinemas c ON s.cinema_id = c.cinema_id
+            WHERE
+                s.cinema_id = $1
+            AND
+            	s.showdate = $2
+            AND
+                s.starts_at >= $3
+            AND
+                ($4::varchar IS NULL OR $4 = ANY(s.badges))
+            ORDER BY
+                s.starts_at, m.title;
+            "#,
+            cinema_id,
+            date,
+            now,
+            badge as Option<Badge>
+        )
+        .fetch_all(conn)
+        .await?
+        .into_iter()
+        .map(|row| MovieSession {
+            movie_id: row.movie_id,
+            title: row.title,
+            session: Session {
+                moskino_session_id: row.moskino_session_id,
+                cinema_name: row.cinema_name,
+                starts_at: row.starts_at,
+                showdate: row.showdate,
+                price: row.price,
+                price_kind: row.price_kind,
+                badges: row.badges,
+            },
+        })
+        .collect();
+
+        if sessions.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(sessions))
+        }
+    }
 
+    // выборка сеансов по фильму по всем кинотеатрам за определенную дату
+    pub async fn q_get_sessions_all(
+        conn: impl sqlx::PgExecutor<'_>,
+        movie_id: i32,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+    ) -> DBResult<Option<Vec<Session>>> {
         let sessions: Vec<Session> = sqlx::query_as!(
             Session,
             r#"
             SELECT
+                COALESCE(s.moskino_session_id, 0) as "moskino_session_id!",
                 c.name as cinema_name,
-                s.showtime as showtime,
+                s.starts_at,
                 s.showdate as showdate,
-                s.price as price
+                s.price as price,
+                s.price_kind as "price_kind: PriceKind",
+                s.badges as "badges: Vec<Badge>"
             FROM
                 moskino.sessions s
             JOIN
@@ -176,13 +562,16 @@ impl DB {
             AND
             	s.showdate = $2
             AND
-                s.showtime >= $3
+                s.starts_at >= $3
+            AND
+                ($4::varchar IS NULL OR $4 = ANY(s.badges))
             ORDER BY
-                cinema_name, showtime;
+                cinema_name, s.starts_at;
             "#,
             movie_id,
             date,
-            time
+            now,
+            badge as Option<Badge>
         )
         .fetch_all(conn)
         .await?;
@@ -199,7 +588,7 @@ impl DB {
             Cinema,
             r#"
             SELECT
-                cinema_id as id, name
+                cinema_id as id, name, address, metro, metro_color
             FROM
                 moskino.cinemas
             WHERE
@@ -216,11 +605,12 @@ impl DB {
         }
     }
 
-    pub async fn q_get_cinema_name_by_id(conn: impl sqlx::PgExecutor<'_>, cinema_id: i32) -> DBResult<Option<String>> {
-        sqlx::query_scalar!(
+    pub async fn q_get_cinema_by_id(conn: impl sqlx::PgExecutor<'_>, cinema_id: i32) -> DBResult<Option<Cinema>> {
+        sqlx::query_as!(
+            Cinema,
             r#"
             SELECT
-                name
+                cinema_id as id, name, address, metro, metro_color
             FROM
                 moskino.cinemas
             WHERE
@@ -232,12 +622,17 @@ impl DB {
         )
         .fetch_optional(conn)
         .await
+        .map_err(Error::from)
     }
 
     // TODO
-    pub async fn q_count_movies_by_cinema(conn: impl sqlx::PgExecutor<'_>, date: NaiveDate, cinema_id: i32) -> DBResult<i64> {
-        let time = time_determine(date);
-
+    pub async fn q_count_movies_by_cinema(
+        conn: impl sqlx::PgExecutor<'_>,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        cinema_id: i32,
+        badge: Option<Badge>,
+    ) -> DBResult<i64> {
         sqlx::query_scalar::<_, i64>(
             r#"
             SELECT COUNT(DISTINCT m.movie_id)
@@ -252,133 +647,182 @@ impl DB {
             AND
                 s.showdate = $2
             AND
-                s.showtime >= $3
+                s.starts_at >= $3
+            AND
+                ($4::varchar IS NULL OR $4 = ANY(s.badges))
             ;"#,
         )
         .bind(cinema_id)
         .bind(date)
-        .bind(time)
+        .bind(now)
+        .bind(badge)
         .fetch_one(conn)
         .await
+        .map_err(Error::from)
     }
 
-    pub async fn q_get_movie_by_id(conn: impl sqlx::PgExecutor<'_>, movie_id: i32) -> DBResult<Movie> {
-        let row = sqlx::query(
+    // None if there is no such movie (e.g. the callback data is outdated)
+    pub async fn q_get_movie_by_id(conn: impl sqlx::PgExecutor<'_>, movie_id: i32) -> DBResult<Option<Movie>> {
+        sqlx::query_as!(
+            Movie,
             r#"
             SELECT
+                movie_id as id,
                 title,
                 year,
                 genre,
                 director,
                 description,
                 href_moskino,
-                href_kinopoisk
+                href_kinopoisk,
+                country,
+                duration,
+                age,
+                tagline
             FROM
                 moskino.movies
             WHERE
                 movie_id = $1;
             "#,
+            movie_id
         )
-        .bind(movie_id)
-        .fetch_one(conn)
-        .await?;
-
-        Ok(Movie {
-            title: row.get("title"),
-            year: row.get("year"),
-            genre: row.get("genre"),
-            director: row.get("director"),
-            description: row.get("description"),
-            href_moskino: row.get("href_moskino"),
-            href_kinopoisk: row.get("href_kinopoisk"),
-            ..Default::default()
-        })
+        .fetch_optional(conn)
+        .await
+        .map_err(Error::from)
     }
 
-    // TODO
     pub async fn q_get_movies_short(
         conn: impl sqlx::PgExecutor<'_>,
         date: NaiveDate,
-        page: i64,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+        sort: MovieSort,
+        keyset: &Keyset,
         items_per_page: i64,
     ) -> DBResult<Option<Vec<MovieShort>>> {
-        let time = time_determine(date);
-        let offset = (page - 1) * items_per_page;
-
-        let movies = sqlx::query_as!(
-            MovieShort,
-            r#"
-            SELECT DISTINCT
-                m.movie_id as id,
-                m.title
-            FROM
-                moskino.movies m
-            JOIN
-                moskino.sessions s ON m.movie_id = s.movie_id
-            WHERE
-                s.showdate = $1
-            AND
-                s.showtime >= $2
-            LIMIT
-                $3
-            OFFSET
-                $4
-            ;"#,
-            date,
-            time,
-            items_per_page,
-            offset
-        )
-        .fetch_all(conn)
-        .await?;
-
-        if movies.is_empty() {
-            Ok(None)
-        } else {
-            Ok(Some(movies))
-        }
+        Self::q_get_movies_page(conn, date, now, None, None, badge, sort, keyset, items_per_page).await
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn q_get_movies_short_by_cinema(
         conn: impl sqlx::PgExecutor<'_>,
         date: NaiveDate,
+        now: DateTime<Utc>,
         cinema_id: i32,
-        page: i64,
+        badge: Option<Badge>,
+        sort: MovieSort,
+        keyset: &Keyset,
         items_per_page: i64,
     ) -> DBResult<Option<Vec<MovieShort>>> {
-        let time = time_determine(date);
-        let offset = (page - 1) * items_per_page;
+        Self::q_get_movies_page(conn, date, now, Some(cinema_id), None, badge, sort, keyset, items_per_page).await
+    }
+
+    // the movies found by the title (see moskino.title_matches)
+    #[allow(clippy::too_many_arguments)]
+    pub async fn q_search_movies(
+        conn: impl sqlx::PgExecutor<'_>,
+        text: &str,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+        sort: MovieSort,
+        keyset: &Keyset,
+        items_per_page: i64,
+    ) -> DBResult<Option<Vec<MovieShort>>> {
+        Self::q_get_movies_page(conn, date, now, None, Some(text), badge, sort, keyset, items_per_page).await
+    }
+
+    // a page of the movies having upcoming sessions (in the cinema and found by the title if set)
+    // titles are compared bytewise (COLLATE "C"), so the order doesn't depend on the locale of the server
+    #[allow(clippy::too_many_arguments)]
+    async fn q_get_movies_page(
+        conn: impl sqlx::PgExecutor<'_>,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        cinema_id: Option<i32>,
+        search: Option<&str>,
+        badge: Option<Badge>,
+        sort: MovieSort,
+        keyset: &Keyset,
+        items_per_page: i64,
+    ) -> DBResult<Option<Vec<MovieShort>>> {
+        // 1 - after the cursor, -1 - before it (the page is taken backwards and reversed)
+        let (direction, cursor) = match keyset {
+            Keyset::First => (0, None),
+            Keyset::After(cursor) => (1, Some(cursor)),
+            Keyset::Before(cursor) => (-1, Some(cursor)),
+        };
 
         let movies = sqlx::query_as!(
             MovieShort,
             r#"
-            SELECT DISTINCT
-                m.movie_id as id,
-                m.title
+            WITH movies AS (
+                SELECT
+                    m.movie_id as id,
+                    m.title,
+                    CASE $5::varchar
+                        WHEN 'showtime' THEN EXTRACT(EPOCH FROM MIN(s.starts_at))::bigint
+                        WHEN 'sessions' THEN -COUNT(*)
+                        WHEN 'release' THEN -COALESCE(m.year, 0)::bigint
+                        WHEN 'relevance' THEN -COALESCE(word_similarity(moskino.search_text($11), moskino.search_text(m.title)) * 1000, 0)::bigint
+                        ELSE 0::bigint
+                    END as key
+                FROM
+                    moskino.movies m
+                JOIN
+                    moskino.sessions s ON m.movie_id = s.movie_id
+                WHERE
+                    s.showdate = $1
+                AND
+                    s.starts_at >= $2
+                AND
+                    ($3::int IS NULL OR s.cinema_id = $3)
+                AND
+                    ($4::varchar IS NULL OR $4 = ANY(s.badges))
+                AND
+                    ($11::text IS NULL OR moskino.title_matches(m.title, $11))
+                GROUP BY
+                    m.movie_id
+            ),
+            page AS (
+                SELECT
+                    id, title, key
+                FROM
+                    movies
+                WHERE
+                    $6::int = 0
+                OR
+                    ($6 > 0 AND (key, title COLLATE "C", id) > ($7::bigint, $8::varchar COLLATE "C", $9::int))
+                OR
+                    ($6 < 0 AND (key, title COLLATE "C", id) < ($7, $8 COLLATE "C", $9))
+                ORDER BY
+                    CASE WHEN $6 < 0 THEN key END DESC,
+                    CASE WHEN $6 < 0 THEN title END COLLATE "C" DESC,
+                    CASE WHEN $6 < 0 THEN id END DESC,
+                    key, title COLLATE "C", id
+                LIMIT
+                    $10
+            )
+            SELECT
+                id as "id!",
+                title as "title!",
+                key as "key!"
             FROM
-                moskino.cinemas c
-            JOIN
-                moskino.sessions s ON c.cinema_id = s.cinema_id
-            JOIN
-                moskino.movies m ON s.movie_id = m.movie_id
-            WHERE
-                c.cinema_id = $1
-            AND
-                s.showdate = $2
-            AND
-                s.showtime >= $3
-            --ORDER BY
-            --    m.title
-            LIMIT
-                $4
-            OFFSET
-                $5
+                page
+            ORDER BY
+                key, title COLLATE "C", id;
             "#,
-            cinema_id,
             date,
-            time,
+            now,
+            cinema_id,
+            badge as Option<Badge>,
+            sort as MovieSort,
+            direction,
+            cursor.map(|cursor| cursor.key),
+            cursor.map(|cursor| cursor.title.as_str()),
+            cursor.map(|cursor| cursor.id),
             items_per_page,
-            offset
+            search
         )
         .fetch_all(conn)
         .await?;
@@ -390,9 +834,12 @@ impl DB {
         }
     }
 
-    pub async fn q_count_movies(conn: impl sqlx::PgExecutor<'_>, date: NaiveDate) -> DBResult<i64> {
-        let time = time_determine(date);
-
+    pub async fn q_count_movies(
+        conn: impl sqlx::PgExecutor<'_>,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+    ) -> DBResult<i64> {
         sqlx::query_scalar::<_, i64>(
             r#"
                 SELECT COUNT(DISTINCT m.movie_id)
@@ -403,18 +850,148 @@ impl DB {
                 WHERE
                     s.showdate = $1
                 AND
-                    s.showtime >= $2
+                    s.starts_at >= $2
+                AND
+                    ($3::varchar IS NULL OR $3 = ANY(s.badges))
             ;"#,
         )
         .bind(date)
-        .bind(time)
+        .bind(now)
+        .bind(badge)
         .fetch_one(conn)
         .await
+        .map_err(Error::from)
     }
 
-    pub async fn insert_user(&self, id: i64, username: Option<&str>) -> DBResult<PgQueryResult> {
-        let (date, time) = datetime_utc3();
+    pub async fn q_count_search_movies(
+        conn: impl sqlx::PgExecutor<'_>,
+        text: &str,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+    ) -> DBResult<i64> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT
+                COUNT(DISTINCT m.movie_id) as "count!"
+            FROM
+                moskino.movies m
+            JOIN
+                moskino.sessions s ON m.movie_id = s.movie_id
+            WHERE
+                s.showdate = $2
+            AND
+                s.starts_at >= $3
+            AND
+                ($4::varchar IS NULL OR $4 = ANY(s.badges))
+            AND
+                moskino.title_matches(m.title, $1)
+            ;"#,
+            text,
+            date,
+            now,
+            badge as Option<Badge>
+        )
+        .fetch_one(conn)
+        .await
+        .map_err(Error::from)
+    }
 
+    // id of the movie already looked up on the sites by web-parser (even if it wasn't found on some of them),
+    // of any year if the schedule has none
+    pub async fn q_get_enriched_movie_id(conn: impl sqlx::PgExecutor<'_>, title: &str, year: Option<i32>) -> DBResult<Option<i32>> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT
+                movie_id
+            FROM
+                moskino.movies
+            WHERE
+                title = $1
+            AND
+                (year = $2 OR $2 IS NULL)
+            AND
+                enriched_at IS NOT NULL
+            ORDER BY
+                enriched_at DESC
+            LIMIT 1;
+            "#,
+            title,
+            year
+        )
+        .fetch_optional(conn)
+        .await
+        .map_err(Error::from)
+    }
+
+    // the text searched by the title, None if the id is unknown
+    pub async fn q_get_search(conn: impl sqlx::PgExecutor<'_>, search_id: i32) -> DBResult<Option<String>> {
+        sqlx::query_scalar!("SELECT text FROM moskino.searches WHERE search_id = $1;", search_id)
+            .fetch_optional(conn)
+            .await
+            .map_err(Error::from)
+    }
+
+    // the latest run of web-parser whatever its date and status
+    pub async fn q_get_latest_run(conn: impl sqlx::PgExecutor<'_>) -> DBResult<Option<ScrapeRun>> {
+        sqlx::query_as!(
+            ScrapeRun,
+            r#"
+            SELECT
+                run_id as id,
+                showdate,
+                started_at,
+                finished_at,
+                status as "status: RunStatus",
+                cinemas,
+                movies,
+                sessions,
+                errors
+            FROM
+                moskino.scrape_runs
+            ORDER BY
+                started_at DESC
+            LIMIT 1;
+            "#,
+        )
+        .fetch_optional(conn)
+        .await
+        .map_err(Error::from)
+    }
+
+    // the latest completed run for the date, i.e. when its schedule was updated
+    pub async fn q_get_latest_run_by_date(conn: impl sqlx::PgExecutor<'_>, date: NaiveDate) -> DBResult<Option<ScrapeRun>> {
+        sqlx::query_as!(
+            ScrapeRun,
+            r#"
+            SELECT
+                run_id as id,
+                showdate,
+                started_at,
+                finished_at,
+                status as "status: RunStatus",
+                cinemas,
+                movies,
+                sessions,
+                errors
+            FROM
+                moskino.scrape_runs
+            WHERE
+                showdate = $1
+            AND
+                status IN ('ok', 'partial')
+            ORDER BY
+                finished_at DESC
+            LIMIT 1;
+            "#,
+            date
+        )
+        .fetch_optional(conn)
+        .await
+        .map_err(Error::from)
+    }
+
+    pub async fn insert_user(&self, id: i64, username: Option<&str>) -> DBResult<PgQueryResult> {
         sqlx::query!(
             r#"
             INSERT INTO
@@ -428,52 +1005,321 @@ impl DB {
                 "#,
             id,
             username,
-            NaiveDateTime::new(date, time)
+            self.clock.now()
+        )
+        .execute(&self.conn)
+        .await
+        .map_err(Error::from)
+    }
+
+    // the dialogue of the chat, unless it was not updated for `ttl`
+    pub async fn q_get_dialogue(&self, chat_id: i64, ttl: Duration) -> DBResult<Option<serde_json::Value>> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT
+                state
+            FROM
+                moskino.dialogues
+            WHERE
+                chat_id = $1
+            AND
+                updated_at >= $2;
+            "#,
+            chat_id,
+            self.clock.now() - ttl
+        )
+        .fetch_optional(&self.conn)
+        .await
+        .map_err(Error::from)
+    }
+
+    pub async fn upsert_dialogue(&self, chat_id: i64, state: &serde_json::Value) -> DBResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO
+                moskino.dialogues (chat_id, state, updated_at)
+            VALUES
+                ($1, $2, $3)
+            ON CONFLICT (chat_id) DO UPDATE
+            SET
+                state = EXCLUDED.state,
+                updated_at = EXCLUDED.updated_at;
+            "#,
+            chat_id,
+            state,
+            self.clock.now()
         )
         .execute(&self.conn)
         .await
+        .map(|_| ())
+        .map_err(Error::from)
+    }
+
+    pub async fn delete_dialogue(&self, chat_id: i64) -> DBResult<()> {
+        sqlx::query!("DELETE FROM moskino.dialogues WHERE chat_id = $1;", chat_id)
+            .execute(&self.conn)
+            .await
+            .map(|_| ())
+            .map_err(Error::from)
+    }
+
+    // the dialogues not updated for `ttl`, returns how many were deleted
+    pub async fn delete_stale_dialogues(&self, ttl: Duration) -> DBResult<u64> {
+        sqlx::query!("DELETE FROM moskino.dialogues WHERE updated_at < $1;", self.clock.now() - ttl)
+            .execute(&self.conn)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(Error::from)
+    }
+
+    // replace the sessions of the cinema for the date with the scraped ones in one transaction:
+    // new sessions are inserted, already stored are updated (matched by the moskino id, so rescheduled ones are moved)
+    // and (if `remove_unseen`) the rest are deleted, e.g. cancelled ones
+    pub async fn replace_sessions(
+        &self,
+        cinema_id: i32,
+        showdate: NaiveDate,
+        sessions: &[(i32, Session)],
+        remove_unseen: bool,
+    ) -> DBResult<SessionsReplaced> {
+        let mut tx = self.conn.begin().await?;
+        let mut replaced = SessionsReplaced::default();
+        let mut seen = vec![];
+
+        for (movie_id, session) in sessions {
+            // the session stored before the moskino ids gets its id
+            sqlx::query!(
+                r#"
+                UPDATE
+                    moskino.sessions
+                SET
+                    moskino_session_id = $1
+                WHERE
+                    session_id = (
+                        SELECT session_id FROM moskino.sessions
+                        WHERE moskino_session_id IS NULL AND cinema_id = $2 AND movie_id = $3 AND starts_at = $4
+                        LIMIT 1
+                    )
+                AND
+                    NOT EXISTS (SELECT 1 FROM moskino.sessions WHERE moskino_session_id = $1);
+                "#,
+                session.moskino_session_id,
+                cinema_id,
+                movie_id,
+                session.starts_at
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            let row = sqlx::query!(
+                r#"
+                INSERT INTO
+                    moskino.sessions (moskino_session_id, cinema_id, movie_id, showdate, starts_at, price, price_kind, badges)
+                VALUES
+                    ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT (moskino_session_id) DO UPDATE
+                SET
+                    cinema_id = excluded.cinema_id,
+                    movie_id = excluded.movie_id,
+                    showdate = excluded.showdate,
+                    starts_at = excluded.starts_at,
+                    price = excluded.price,
+                    price_kind = excluded.price_kind,
+                    badges = excluded.badges
+                RETURNING
+                    session_id, (xmax = 0) as "inserted!";
+                "#,
+                session.moskino_session_id,
+                cinema_id,
+                movie_id,
+                showdate,
+                session.starts_at,
+                session.price,
+                session.price_kind as PriceKind,
+                &session.badges as &[Badge]
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+
+            if row.inserted {
+                replaced.inserted += 1;
+            } else {
+                replaced.existing += 1;
+            }
+            seen.push(row.session_id);
+        }
+
+        if remove_unseen {
+            replaced.removed = sqlx::query!(
+                r#"
+                DELETE FROM
+                    moskino.sessions
+                WHERE
+                    cinema_id = $1
+                AND
+                    showdate = $2
+                AND
+                    NOT (session_id = ANY($3));
+                "#,
+                cinema_id,
+                showdate,
+                &seen
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+
+        tx.commit().await?;
+
+        Ok(replaced)
     }
 
-    pub async fn insert_session(&self, session: &Session, cinema_id: i32, movie_id: i32) -> DBResult<PgQueryResult> {
+    // delete the sessions of the date in the cinemas absent on the schedule, e.g. the ones who cancelled the whole day
+    // returns the number of deleted sessions
+    pub async fn remove_sessions_of_unseen_cinemas(&self, showdate: NaiveDate, seen_cinema_ids: &[i32]) -> DBResult<u64> {
         sqlx::query!(
+            r#"
+            DELETE FROM
+                moskino.sessions
+            WHERE
+                showdate = $1
+            AND
+                NOT (cinema_id = ANY($2));
+            "#,
+            showdate,
+            seen_cinema_ids
+        )
+        .execute(&self.conn)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::from)
+    }
+
+    // register a started run of web-parser
+    // returns id of the run
+    pub async fn insert_scrape_run(&self, showdate: NaiveDate) -> DBResult<i32> {
+        sqlx::query_scalar!(
             r#"
             INSERT INTO
-                moskino.sessions (cinema_id, movie_id, showdate, showtime, price)
+                moskino.scrape_runs (showdate, started_at, status)
             VALUES
-                ($1, $2, $3, $4, $5);
+                ($1, $2, $3)
+            RETURNING
+                run_id;
             "#,
-            cinema_id,
-            movie_id,
-            session.showdate,
-            session.showtime,
-            session.price
+            showdate,
+            self.clock.now(),
+            RunStatus::Running as RunStatus
+        )
+        .fetch_one(&self.conn)
+        .await
+        .map_err(Error::from)
+    }
+
+    pub async fn finish_scrape_run(
+        &self,
+        run_id: i32,
+        status: RunStatus,
+        cinemas: i32,
+        movies: i32,
+        sessions: i32,
+        errors: i32,
+    ) -> DBResult<PgQueryResult> {
+        sqlx::query!(
+            r#"
+            UPDATE
+                moskino.scrape_runs
+            SET
+                finished_at = $2,
+                status = $3,
+                cinemas = $4,
+                movies = $5,
+                sessions = $6,
+                errors = $7
+            WHERE
+                run_id = $1;
+            "#,
+            run_id,
+            self.clock.now(),
+            status as RunStatus,
+            cinemas,
+            movies,
+            sessions,
+            errors
         )
         .execute(&self.conn)
         .await
+        .map_err(Error::from)
     }
 
     // insert cinema into moskino.cinema
+    // an already existing cinema gets the newly known contacts (known ones are kept)
     // returns id of inserted cinema (or already existed)
     pub async fn insert_cinema(&self, cinema: &Cinema) -> DBResult<i32> {
         sqlx::query_scalar!(
             r#"
             INSERT INTO
-                moskino.cinemas (name, is_active)
+                moskino.cinemas (name, address, metro, metro_color, is_active)
             VALUES
-                ($1, true)
+                ($1, $2, $3, $4, true)
             ON CONFLICT (name) DO UPDATE
             SET
-                name = excluded.name
+                address = COALESCE(excluded.address, cinemas.address),
+                metro = COALESCE(excluded.metro, cinemas.metro),
+                metro_color = COALESCE(excluded.metro_color, cinemas.metro_color),
+                is_active = true
             RETURNING
                 cinema_id;
             "#,
-            cinema.name
+            cinema.name,
+            cinema.address,
+            cinema.metro,
+            cinema.metro_color
         )
         .fetch_one(&self.conn)
         .await
+        .map_err(Error::from)
+    }
+
+    // the movie was looked up on the sites, whatever was found
+    pub async fn set_movie_enriched(&self, movie_id: i32) -> DBResult<()> {
+        sqlx::query!(
+            "UPDATE moskino.movies SET enriched_at = $2 WHERE movie_id = $1;",
+            movie_id,
+            self.clock.now()
+        )
+        .execute(&self.conn)
+        .await
+        .map(|_| ())
+        .map_err(Error::from)
+    }
+
+    // the same text gets the same id
+    // returns id of the search
+    pub async fn insert_search(&self, text: &str) -> DBResult<i32> {
+        sqlx::query_scalar!(
+            r#"
+            INSERT INTO
+                moskino.searches (text, created_at)
+            VALUES
+                ($1, $2)
+            ON CONFLICT (text) DO UPDATE
+            SET
+                text = excluded.text
+            RETURNING
+                search_id;
+            "#,
+            text,
+            self.clock.now()
+        )
+        .fetch_one(&self.conn)
+        .await
+        .map_err(Error::from)
     }
 
     // insert movie into moskino.movie
+    // an already existing movie gets the newly known details (known ones are kept)
     // returns id of inserted movie (or already existed)
     pub async fn insert_movie(&self, movie: &Movie) -> DBResult<i32> {
         sqlx::query_scalar!(
@@ -484,7 +1330,15 @@ impl DB {
                 ($1, COALESCE($2, 0), $3, $4, $5, $6, $7, $8, $9, $10, $11)
             ON CONFLICT (title, year) DO UPDATE
             SET
-                title = excluded.title
+                genre = COALESCE(excluded.genre, movies.genre),
+                country = COALESCE(excluded.country, movies.country),
+                duration = COALESCE(excluded.duration, movies.duration),
+                age = COALESCE(excluded.age, movies.age),
+                director = COALESCE(excluded.director, movies.director),
+                tagline = COALESCE(excluded.tagline, movies.tagline),
+                description = COALESCE(excluded.description, movies.description),
+                href_moskino = COALESCE(excluded.href_moskino, movies.href_moskino),
+                href_kinopoisk = COALESCE(excluded.href_kinopoisk, movies.href_kinopoisk)
             RETURNING
                 movie_id;
             "#,
@@ -502,30 +1356,85 @@ impl DB {
         )
         .fetch_one(&self.conn)
         .await
+        .map_err(Error::from)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    // use super::*;
-
-    // #[tokio::test]
-    // async fn test_q_get_movie_by_id() {
-    //     let db = DB::new().await.unwrap();
-
-    //     let movie_test = Movie {
-    //         title: "Test Movie".to_string(),
-    //         year: Some(2023),
-    //         genre: Some("Action".to_string()),
-    //         director: Some("Test Director".to_string()),
-    //         description: Some("This is a description of the test movie.".to_string()),
-    //         href_moskino: Some("http://example.com/moskino/test-movie".to_string()),
-    //         href_kinopoisk: Some("http://example.com/kinopoisk/test-movie".to_string()),
-    //         ..Default::default()
-    //     };
-
-    //     let movie = DB::q_get_movie_by_id(&db.conn, 1).await.unwrap();
-
-    //     assert_eq!(movie, movie_test);
-    // }
+    use super::*;
+
+    #[test]
+    fn test_movie_description() {
+        let mut movie = Movie {
+            title: "По щучьему велению".to_string(),
+            year: Some(2023),
+            country: Some("Россия".to_string()),
+            duration: Some(115),
+            age: Some(6),
+            tagline: Some("Емеля и волшебная щука".to_string()),
+            description: Some("Сказка".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            movie.description(),
+            "По щучьему велению\n«Емеля и волшебная щука»\n\nГод: 2023\nСтрана: Россия\nДлительность: 1 ч 55 мин\nВозраст: 6+\n\nСказка\n"
+        );
+
+        // the year is unknown
+        movie.year = Some(0);
+        movie.duration = Some(45);
+        assert!(!movie.description().contains("Год"));
+        assert!(movie.description().contains("Длительность: 45 мин"));
+    }
+
+    #[test]
+    fn test_cinema_location() {
+        let mut cinema = Cinema {
+            name: "Сатурн".to_string(),
+            address: Some("Снежная ул., д. 18".to_string()),
+            metro: Some("Свиблово".to_string()),
+            metro_color: Some("#EF8532".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(cinema.location().as_deref(), Some("Снежная ул., д. 18 • 🟠 Свиблово"));
+
+        // Серпуховско-Тимирязевская, Кольцевая
+        cinema.metro_color = Some("#ADACAC".to_string());
+        assert_eq!(cinema.metro_marker(), "⚪");
+        cinema.metro_color = Some("#8D5B2D".to_string());
+        assert_eq!(cinema.metro_marker(), "🟤");
+        cinema.metro_color = Some("#e42".to_string());
+        assert_eq!(cinema.metro_marker(), "🔴");
+        cinema.metro_color = Some("#e4".to_string());
+        assert_eq!(cinema.metro_marker(), "Ⓜ️");
+
+        cinema.metro_color = None;
+        cinema.address = None;
+        assert_eq!(cinema.location().as_deref(), Some("Ⓜ️ Свиблово"));
+    }
+
+    #[test]
+    fn test_session_is_upcoming() {
+        use chrono::TimeZone;
+        use clock::FixedClock;
+
+        let showdate = NaiveDate::from_ymd_opt(2023, 12, 23).unwrap();
+        let session = Session {
+            moskino_session_id: 96619401,
+            cinema_name: "Сатурн".to_string(),
+            starts_at: tools::session_start(showdate, NaiveTime::from_hms_opt(0, 30, 0).unwrap()),
+            showdate,
+            price: 300,
+            price_kind: PriceKind::Paid,
+            badges: vec![],
+        };
+        assert_eq!(session.showtime(), NaiveTime::from_hms_opt(0, 30, 0).unwrap());
+
+        // 23:59 of the 23rd and 00:29 of the 24th in Moscow
+        assert!(session.is_upcoming(&FixedClock(Utc.with_ymd_and_hms(2023, 12, 23, 20, 59, 0).unwrap())));
+        assert!(session.is_upcoming(&FixedClock(Utc.with_ymd_and_hms(2023, 12, 23, 21, 29, 0).unwrap())));
+        assert!(!session.is_upcoming(&FixedClock(Utc.with_ymd_and_hms(2023, 12, 23, 21, 31, 0).unwrap())));
+    }
 }
@@ -0,0 +1,692 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::{collections::HashMap, sync::Arc, sync::Mutex};
+
+use super::clock::Clock;
+use super::repository::{DialogueRepository, ScheduleRepository};
+use super::{
+    Badge, Cinema, DBResult, Error, Keyset, Movie, MovieSession, MovieShort, MovieSort, RunStatus, ScrapeRun, Session, SessionsReplaced,
+};
+
+// the storage for tests, does what the postgres queries do
+#[derive(Debug)]
+pub struct MemoryRepository {
+    clock: Arc<dyn Clock>,
+    tables: Mutex<Tables>,
+}
+
+#[derive(Debug, Default)]
+struct Tables {
+    // ids are positions + 1
+    cinemas: Vec<Cinema>,
+    movies: Vec<Movie>,
+    runs: Vec<ScrapeRun>,
+    sessions: Vec<StoredSession>,
+    users: HashMap<i64, Option<String>>,
+    // movie id: when it was enriched
+    enriched: HashMap<i32, DateTime<Utc>>,
+    searches: Vec<String>,
+    // the state and when it was updated
+    dialogues: HashMap<i64, (serde_json::Value, DateTime<Utc>)>,
+}
+
+#[derive(Debug)]
+struct StoredSession {
+    session_id: i32,
+    cinema_id: i32,
+    movie_id: i32,
+    session: Session,
+}
+
+impl MemoryRepository {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            tables: Mutex::new(Tables::default()),
+        }
+    }
+
+    fn tables(&self) -> std::sync::MutexGuard<'_, Tables> {
+        self.tables.lock().unwrap()
+    }
+}
+
+impl Tables {
+    fn cinema(&self, cinema_id: i32) -> DBResult<&Cinema> {
+        self.cinemas.iter().find(|cinema| cinema.id == cinema_id).ok_or(Error::NotFound)
+    }
+
+    fn movie(&self, movie_id: i32) -> DBResult<&Movie> {
+        self.movies.iter().find(|movie| movie.id == movie_id).ok_or(Error::NotFound)
+    }
+
+    // the upcoming sessions of the date ordered as the schedule is, with the name of the cinema
+    fn sessions(&self, date: NaiveDate, now: DateTime<Utc>, badge: Option<Badge>) -> DBResult<Vec<(i32, i32, Session)>> {
+        let mut sessions: Vec<(i32, i32, Session)> = self
+            .sessions
+            .iter()
+            .filter(|stored| stored.session.showdate == date && stored.session.starts_at >= now)
+            .filter(|stored| badge.map_or(true, |badge| stored.session.badges.contains(&badge)))
+            .map(|stored| {
+                let session = Session {
+                    cinema_name: self.cinema(stored.cinema_id)?.name.clone(),
+                    ..stored.session.clone()
+                };
+                Ok((stored.cinema_id, stored.movie_id, session))
+            })
+            .collect::<DBResult<_>>()?;
+
+        sessions.sort_by(|(_, _, a), (_, _, b)| (&a.cinema_name, a.starts_at).cmp(&(&b.cinema_name, b.starts_at)));
+        Ok(sessions)
+    }
+
+    // the distinct movies having the sessions (and found by the title if searched), sorted
+    fn movies(&self, sessions: &[(i32, i32, Session)], sort: MovieSort, search: Option<&str>) -> DBResult<Vec<MovieShort>> {
+        let mut ids: Vec<i32> = sessions.iter().map(|(_, movie_id, _)| *movie_id).collect();
+        ids.sort();
+        ids.dedup();
+
+        let mut movies = vec![];
+        for id in ids {
+            let movie = self.movie(id)?;
+            let similarity = match search {
+                Some(text) => match search_similarity(&movie.title, text) {
+                    Some(similarity) => similarity,
+                    None => continue,
+                },
+                None => 0.0,
+            };
+            let starts = sessions
+                .iter()
+                .filter(|(_, movie_id, _)| *movie_id == id)
+                .map(|(.., s)| s.starts_at);
+
+            let key = match sort {
+                MovieSort::Title => 0,
+                MovieSort::Showtime => starts.min().unwrap().timestamp(),
+                MovieSort::Sessions => -(starts.count() as i64),
+                MovieSort::Release => -(movie.year.unwrap_or(0) as i64),
+                MovieSort::Relevance => -(similarity * 1000.0).round() as i64,
+            };
+
+            movies.push(MovieShort {
+                id,
+                title: movie.title.clone(),
+                key,
+            });
+        }
+
+        movies.sort_by_key(|movie| movie.cursor());
+        Ok(movies)
+    }
+}
+
+// roughly what moskino.title_matches does (ё is е): the title contains the text
+// or every word of the text is in the title with one typo at most
+fn search_similarity(title: &str, text: &str) -> Option<f64> {
+    let normalize = |text: &str| text.to_lowercase().replace('ё', "е");
+    let (title, text) = (normalize(title), normalize(text.trim()));
+
+    if text.is_empty() {
+        return None;
+    }
+    if title.contains(&text) {
+        return Some(1.0);
+    }
+
+    let mut typos = 0;
+    for word in text.split_whitespace() {
+        typos += title
+            .split_whitespace()
+            .map(|title_word| distance(word, title_word))
+            .filter(|&distance| distance == 0 || (distance == 1 && word.chars().count() > 3))
+            .min()?;
+    }
+    Some(1.0 - typos as f64 / text.chars().count() as f64)
+}
+
+// levenshtein distance
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb { prev } else { 1 + prev.min(row[j]).min(row[j + 1]) };
+            prev = current;
+        }
+    }
+    row[b.len()]
+}
+
+fn page_of(movies: Vec<MovieShort>, keyset: &Keyset, items_per_page: i64) -> Option<Vec<MovieShort>> {
+    let items_per_page = items_per_page.max(0) as usize;
+
+    let movies: Vec<MovieShort> = match keyset {
+        Keyset::First => movies.into_iter().take(items_per_page).collect(),
+        Keyset::After(cursor) => movies
+            .into_iter()
+            .filter(|movie| movie.cursor() > *cursor)
+            .take(items_per_page)
+            .collect(),
+        Keyset::Before(cursor) => {
+            let before: Vec<MovieShort> = movies.into_iter().filter(|movie| movie.cursor() < *cursor).collect();
+            let skip = before.len().saturating_sub(items_per_page);
+            before.into_iter().skip(skip).collect()
+        }
+    };
+
+    some_if_any(movies)
+}
+
+fn some_if_any<T>(items: Vec<T>) -> Option<Vec<T>> {
+    if items.is_empty() {
+        None
+    } else {
+        Some(items)
+    }
+}
+
+#[async_trait]
+impl ScheduleRepository for MemoryRepository {
+    fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    async fn q_get_sessions_by_cinema(
+        &self,
+        movie_id: i32,
+        cinema_id: i32,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+    ) -> DBResult<Option<Vec<Session>>> {
+        let sessions = self.tables().sessions(date, now, badge)?;
+
+        Ok(some_if_any(
+            sessions
+                .into_iter()
+                .filter(|(cinema, movie, _)| *cinema == cinema_id && *movie == movie_id)
+                .map(|(.., session)| session)
+                .collect(),
+        ))
+    }
+
+    async fn q_get_sessions_all(
+        &self,
+        movie_id: i32,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+    ) -> DBResult<Option<Vec<Session>>> {
+        let sessions = self.tables().sessions(date, now, badge)?;
+
+        Ok(some_if_any(
+            sessions
+                .into_iter()
+                .filter(|(_, movie, _)| *movie == movie_id)
+                .map(|(.., session)| session)
+                .collect(),
+        ))
+    }
+
+    async fn q_get_sessions_of_cinema(
+        &self,
+        cinema_id: i32,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+    ) -> DBResult<Option<Vec<MovieSession>>> {
+        let tables = self.tables();
+        let mut sessions = vec![];
+        for (cinema, movie_id, session) in tables.sessions(date, now, badge)? {
+            if cinema == cinema_id {
+                let title = tables.movie(movie_id)?.title.clone();
+                sessions.push(MovieSession { movie_id, title, session });
+            }
+        }
+        sessions.sort_by(|a, b| (a.session.starts_at, &a.title).cmp(&(b.session.starts_at, &b.title)));
+
+        Ok(some_if_any(sessions))
+    }
+
+    async fn q_get_cinemas(&self) -> DBResult<Option<Vec<Cinema>>> {
+        Ok(some_if_any(self.tables().cinemas.clone()))
+    }
+
+    async fn q_get_cinema_by_id(&self, cinema_id: i32) -> DBResult<Option<Cinema>> {
+        Ok(self.tables().cinemas.iter().find(|cinema| cinema.id == cinema_id).cloned())
+    }
+
+    async fn q_count_movies_by_cinema(&self, date: NaiveDate, now: DateTime<Utc>, cinema_id: i32, badge: Option<Badge>) -> DBResult<i64> {
+        let tables = self.tables();
+        let mut sessions = tables.sessions(date, now, badge)?;
+        sessions.retain(|(cinema, ..)| *cinema == cinema_id);
+
+        Ok(tables.movies(&sessions, MovieSort::Title, None)?.len() as i64)
+    }
+
+    async fn q_get_movie_by_id(&self, movie_id: i32) -> DBResult<Option<Movie>> {
+        let tables = self.tables();
+
+        Ok(movie_id.checked_sub(1).and_then(|index| tables.movies.get(index as usize)).cloned())
+    }
+
+    async fn q_get_movies_short(
+        &self,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+        sort: MovieSort,
+        keyset: &Keyset,
+        items_per_page: i64,
+    ) -> DBResult<Option<Vec<MovieShort>>> {
+        let tables = self.tables();
+        let sessions = tables.sessions(date, now, badge)?;
+
+        Ok(page_of(tables.movies(&sessions, sort, None)?, keyset, items_per_page))
+    }
+
+    async fn q_get_movies_short_by_cinema(
+        &self,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        cinema_id: i32,
+        badge: Option<Badge>,
+        sort: MovieSort,
+        keyset: &Keyset,
+        items_per_page: i64,
+    ) -> DBResult<Option<Vec<MovieShort>>> {
+        let tables = self.tables();
+        let mut sessions = tables.sessions(date, now, badge)?;
+        sessions.retain(|(cinema, ..)| *cinema == cinema_id);
+
+        Ok(page_of(tables.movies(&sessions, sort, None)?, keyset, items_per_page))
+    }
+
+    async fn q_count_movies(&self, date: NaiveDate, now: DateTime<Utc>, badge: Option<Badge>) -> DBResult<i64> {
+        let tables = self.tables();
+        let sessions = tables.sessions(date, now, badge)?;
+
+        Ok(tables.movies(&sessions, MovieSort::Title, None)?.len() as i64)
+    }
+
+    async fn q_search_movies(
+        &self,
+        text: &str,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+        sort: MovieSort,
+        keyset: &Keyset,
+        items_per_page: i64,
+    ) -> DBResult<Option<Vec<MovieShort>>> {
+        let tables = self.tables();
+        let sessions = tables.sessions(date, now, badge)?;
+
+        Ok(page_of(tables.movies(&sessions, sort, Some(text))?, keyset, items_per_page))
+    }
+
+    async fn q_count_search_movies(&self, text: &str, date: NaiveDate, now: DateTime<Utc>, badge: Option<Badge>) -> DBResult<i64> {
+        let tables = self.tables();
+        let sessions = tables.sessions(date, now, badge)?;
+
+        Ok(tables.movies(&sessions, MovieSort::Title, Some(text))?.len() as i64)
+    }
+
+    async fn q_get_enriched_movie_id(&self, title: &str, year: Option<i32>) -> DBResult<Option<i32>> {
+        let tables = self.tables();
+
+        Ok(tables
+            .movies
+            .iter()
+            .filter(|movie| movie.title == title && (year.is_none() || movie.year == year))
+            .filter_map(|movie| tables.enriched.get(&movie.id).map(|enriched_at| (enriched_at, movie.id)))
+            .max()
+            .map(|(_, movie_id)| movie_id))
+    }
+
+    async fn q_get_search(&self, search_id: i32) -> DBResult<Option<String>> {
+        let i = usize::try_from(search_id - 1).ok();
+        Ok(i.and_then(|i| self.tables().searches.get(i).cloned()))
+    }
+
+    async fn q_get_latest_run(&self) -> DBResult<Option<ScrapeRun>> {
+        Ok(self.tables().runs.iter().max_by_key(|run| (run.started_at, run.id)).cloned())
+    }
+
+    async fn q_get_latest_run_by_date(&self, date: NaiveDate) -> DBResult<Option<ScrapeRun>> {
+        Ok(self
+            .tables()
+            .runs
+            .iter()
+            .filter(|run| run.showdate == date && matches!(run.status, RunStatus::Ok | RunStatus::Partial))
+            .max_by_key(|run| (run.finished_at, run.id))
+            .cloned())
+    }
+
+    async fn insert_user(&self, id: i64, username: Option<&str>) -> DBResult<()> {
+        self.tables().users.insert(id, username.map(String::from));
+        Ok(())
+    }
+
+    async fn replace_sessions(
+        &self,
+        cinema_id: i32,
+        showdate: NaiveDate,
+        sessions: &[(i32, Session)],
+        remove_unseen: bool,
+    ) -> DBResult<SessionsReplaced> {
+        let mut tables = self.tables();
+        let mut replaced = SessionsReplaced::default();
+        let mut seen = vec![];
+
+        for (movie_id, session) in sessions {
+            let session = Session {
+                showdate,
+                ..session.clone()
+            };
+
+            match tables
+                .sessions
+                .iter_mut()
+                .find(|stored| stored.session.moskino_session_id == session.moskino_session_id)
+            {
+                Some(stored) => {
+                    stored.cinema_id = cinema_id;
+                    stored.movie_id = *movie_id;
+                    stored.session = session;
+                    replaced.existing += 1;
+                    seen.push(stored.session_id);
+                }
+                None => {
+                    let session_id = tables.sessions.iter().map(|stored| stored.session_id).max().unwrap_or(0) + 1;
+                    tables.sessions.push(StoredSession {
+                        session_id,
+                        cinema_id,
+                        movie_id: *movie_id,
+                        session,
+                    });
+                    replaced.inserted += 1;
+                    seen.push(session_id);
+                }
+            }
+        }
+
+        if remove_unseen {
+            let before = tables.sessions.len();
+            tables
+                .sessions
+                .retain(|stored| stored.cinema_id != cinema_id || stored.session.showdate != showdate || seen.contains(&stored.session_id));
+            replaced.removed = (before - tables.sessions.len()) as u64;
+        }
+
+        Ok(replaced)
+    }
+
+    async fn remove_sessions_of_unseen_cinemas(&self, showdate: NaiveDate, seen_cinema_ids: &[i32]) -> DBResult<u64> {
+        let mut tables = self.tables();
+        let before = tables.sessions.len();
+
+        tables
+            .sessions
+            .retain(|stored| stored.session.showdate != showdate || seen_cinema_ids.contains(&stored.cinema_id));
+
+        Ok((before - tables.sessions.len()) as u64)
+    }
+
+    async fn insert_scrape_run(&self, showdate: NaiveDate) -> DBResult<i32> {
+        let mut tables = self.tables();
+        let id = tables.runs.len() as i32 + 1;
+
+        tables.runs.push(ScrapeRun {
+            id,
+            showdate,
+            started_at: self.clock.now(),
+            finished_at: None,
+            status: RunStatus::Running,
+            cinemas: 0,
+            movies: 0,
+            sessions: 0,
+            errors: 0,
+        });
+
+        Ok(id)
+    }
+
+    async fn finish_scrape_run(
+        &self,
+        run_id: i32,
+        status: RunStatus,
+        cinemas: i32,
+        movies: i32,
+        sessions: i32,
+        errors: i32,
+    ) -> DBResult<()> {
+        if let Some(run) = self.tables().runs.iter_mut().find(|run| run.id == run_id) {
+            *run = ScrapeRun {
+                finished_at: Some(self.clock.now()),
+                status,
+                cinemas,
+                movies,
+                sessions,
+                errors,
+                ..run.clone()
+            };
+        }
+        Ok(())
+    }
+
+    async fn insert_cinema(&self, cinema: &Cinema) -> DBResult<i32> {
+        let mut tables = self.tables();
+
+        match tables.cinemas.iter_mut().find(|stored| stored.name == cinema.name) {
+            Some(stored) => {
+                stored.address = cinema.address.clone().or(stored.address.take());
+                stored.metro = cinema.metro.clone().or(stored.metro.take());
+                stored.metro_color = cinema.metro_color.clone().or(stored.metro_color.take());
+                Ok(stored.id)
+            }
+            None => {
+                let id = tables.cinemas.len() as i32 + 1;
+                tables.cinemas.push(Cinema { id, ..cinema.clone() });
+                Ok(id)
+            }
+        }
+    }
+
+    async fn insert_movie(&self, movie: &Movie) -> DBResult<i32> {
+        let mut tables = self.tables();
+        let year = Some(movie.year.unwrap_or(0));
+
+        match tables
+            .movies
+            .iter_mut()
+            .position(|stored| stored.title == movie.title && stored.year == year)
+        {
+            Some(index) => {
+                let stored = &mut tables.movies[index];
+                stored.genre = movie.genre.clone().or(stored.genre.take());
+                stored.country = movie.country.clone().or(stored.country.take());
+                stored.duration = movie.duration.or(stored.duration);
+                stored.age = movie.age.or(stored.age);
+                stored.director = movie.director.clone().or(stored.director.take());
+                stored.tagline = movie.tagline.clone().or(stored.tagline.take());
+                stored.description = movie.description.clone().or(stored.description.take());
+                stored.href_moskino = movie.href_moskino.clone().or(stored.href_moskino.take());
+                stored.href_kinopoisk = movie.href_kinopoisk.clone().or(stored.href_kinopoisk.take());
+                Ok(index as i32 + 1)
+            }
+            None => {
+                let id = tables.movies.len() as i32 + 1;
+                tables.movies.push(Movie { id, year, ..movie.clone() });
+                Ok(tables.movies.len() as i32)
+            }
+        }
+    }
+
+    async fn set_movie_enriched(&self, movie_id: i32) -> DBResult<()> {
+        let mut tables = self.tables();
+        let now = self.clock.now();
+        if tables.movie(movie_id).is_ok() {
+            tables.enriched.insert(movie_id, now);
+        }
+        Ok(())
+    }
+
+    async fn insert_search(&self, text: &str) -> DBResult<i32> {
+        let mut tables = self.tables();
+
+        match tables.searches.iter().position(|search| search == text) {
+            Some(i) => Ok(i as i32 + 1),
+            None => {
+                tables.searches.push(text.to_string());
+                Ok(tables.searches.len() as i32)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl DialogueRepository for MemoryRepository {
+    async fn q_get_dialogue(&self, chat_id: i64, ttl: Duration) -> DBResult<Option<serde_json::Value>> {
+        let since = self.clock.now() - ttl;
+
+        Ok(self
+            .tables()
+            .dialogues
+            .get(&chat_id)
+            .filter(|(_, updated_at)| *updated_at >= since)
+            .map(|(state, _)| state.clone()))
+    }
+
+    async fn upsert_dialogue(&self, chat_id: i64, state: &serde_json::Value) -> DBResult<()> {
+        self.tables().dialogues.insert(chat_id, (state.clone(), self.clock.now()));
+        Ok(())
+    }
+
+    async fn delete_dialogue(&self, chat_id: i64) -> DBResult<()> {
+        self.tables().dialogues.remove(&chat_id);
+        Ok(())
+    }
+
+    async fn delete_stale_dialogues(&self, ttl: Duration) -> DBResult<u64> {
+        let since = self.clock.now() - ttl;
+        let mut tables = self.tables();
+        let before = tables.dialogues.len();
+        tables.dialogues.retain(|_, (_, updated_at)| *updated_at >= since);
+
+        Ok((before - tables.dialogues.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::tools::session_start;
+    use crate::PriceKind;
+    use chrono::{NaiveTime, TimeZone};
+
+    fn session(id: i64, date: NaiveDate, time: &str, badges: Vec<Badge>) -> Session {
+        Session {
+            moskino_session_id: id,
+            cinema_name: String::new(),
+            starts_at: session_start(date, NaiveTime::parse_from_str(time, "%H:%M").unwrap()),
+            showdate: date,
+            price: 250,
+            price_kind: PriceKind::Paid,
+            badges,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_memory_repository() {
+        // 14:00 in Moscow
+        let clock = FixedClock(Utc.with_ymd_and_hms(2023, 12, 23, 11, 0, 0).unwrap());
+        let repo = MemoryRepository::new(Arc::new(clock));
+        let date = NaiveDate::from_ymd_opt(2023, 12, 23).unwrap();
+        let now = repo.clock().now();
+
+        let saturn = repo
+            .insert_cinema(&Cinema {
+                name: "Сатурн".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let psy = repo
+            .insert_movie(&Movie {
+                title: "Бешеные псы".to_string(),
+                year: Some(1991),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let pike = repo
+            .insert_movie(&Movie {
+                title: "По щучьему велению".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let sessions = vec![
+            (pike, session(1, date, "13:00", vec![Badge::TwoD])),
+            (psy, session(2, date, "21:30", vec![Badge::TwoD, Badge::Subtitles])),
+            (psy, session(3, date, "00:30", vec![Badge::TwoD])),
+        ];
+        let replaced = repo.replace_sessions(saturn, date, &sessions, true).await.unwrap();
+        assert_eq!(replaced.inserted, 3);
+
+        // 13:00 is over
+        assert_eq!(repo.q_count_movies(date, now, None).await.unwrap(), 1);
+        assert_eq!(
+            repo.q_count_movies_by_cinema(date, now, saturn, Some(Badge::Subtitles))
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(repo.q_count_movies(date, now, Some(Badge::ThreeD)).await.unwrap(), 0);
+
+        // after midnight goes last
+        let psy_sessions = repo.q_get_sessions_by_cinema(psy, saturn, date, now, None).await.unwrap().unwrap();
+        let ids: Vec<i64> = psy_sessions.iter().map(|session| session.moskino_session_id).collect();
+        assert_eq!(ids, vec![2, 3]);
+        assert_eq!(psy_sessions[0].cinema_name, "Сатурн");
+
+        // a cancelled session is removed
+        let replaced = repo.replace_sessions(saturn, date, &sessions[1..2], true).await.unwrap();
+        assert_eq!((replaced.inserted, replaced.existing, replaced.removed), (0, 1, 2));
+        assert_eq!(repo.q_get_sessions_all(psy, date, now, None).await.unwrap().unwrap().len(), 1);
+        assert!(repo.q_get_sessions_all(pike, date, now, None).await.unwrap().is_none());
+
+        // the movie stored without a year
+        let movie = repo.q_get_movie_by_id(pike).await.unwrap().unwrap();
+        assert_eq!((movie.id, movie.year), (pike, Some(0)));
+        assert!(repo.q_get_movie_by_id(100).await.unwrap().is_none());
+
+        // the cinema is gone from the schedule of the date
+        assert_eq!(repo.remove_sessions_of_unseen_cinemas(date, &[saturn]).await.unwrap(), 0);
+        assert_eq!(repo.remove_sessions_of_unseen_cinemas(date, &[]).await.unwrap(), 1);
+        assert_eq!(repo.q_count_movies(date, now, None).await.unwrap(), 0);
+
+        // a session of an unknown cinema
+        repo.replace_sessions(100, date, &sessions[1..2], false).await.unwrap();
+        assert!(matches!(repo.q_count_movies(date, now, None).await, Err(Error::NotFound)));
+    }
+
+    #[test]
+    fn test_search_similarity() {
+        assert_eq!(search_similarity("Ёлки 10", "елки"), Some(1.0));
+        assert_eq!(search_similarity("Олдбой", "ОЛДБОЙ"), Some(1.0));
+        // a typo
+        assert!(search_similarity("Олдбой", "олдбй").unwrap() < 1.0);
+        assert!(search_similarity("Бешеные псы", "бешенные псы").is_some());
+        assert!(search_similarity("Бешеные псы", "псв").is_none());
+        assert!(search_similarity("Олдбой", " ").is_none());
+    }
+}
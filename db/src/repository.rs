@@ -0,0 +1,332 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+use super::clock::Clock;
+use super::{
+    Badge, Cinema, DBResult, Keyset, Movie, MovieSession, MovieShort, MovieSort, RunStatus, ScrapeRun, Session, SessionsReplaced, DB,
+};
+
+// everything the bot and web-parser need from the storage,
+// DB is the postgres one, MemoryRepository is for tests
+#[async_trait]
+pub trait ScheduleRepository: Send + Sync {
+    // the current moment for 'upcoming' sessions, 'today' and the run times
+    fn clock(&self) -> &dyn Clock;
+
+    async fn q_get_sessions_by_cinema(
+        &self,
+        movie_id: i32,
+        cinema_id: i32,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+    ) -> DBResult<Option<Vec<Session>>>;
+
+    async fn q_get_sessions_all(
+        &self,
+        movie_id: i32,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+    ) -> DBResult<Option<Vec<Session>>>;
+
+    // all the sessions of the cinema with their movies, by the showtime
+    async fn q_get_sessions_of_cinema(
+        &self,
+        cinema_id: i32,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+    ) -> DBResult<Option<Vec<MovieSession>>>;
+
+    async fn q_get_cinemas(&self) -> DBResult<Option<Vec<Cinema>>>;
+
+    async fn q_get_cinema_by_id(&self, cinema_id: i32) -> DBResult<Option<Cinema>>;
+
+    async fn q_count_movies_by_cinema(&self, date: NaiveDate, now: DateTime<Utc>, cinema_id: i32, badge: Option<Badge>) -> DBResult<i64>;
+
+    async fn q_get_movie_by_id(&self, movie_id: i32) -> DBResult<Option<Movie>>;
+
+    async fn q_get_movies_short(
+        &self,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+        sort: MovieSort,
+        keyset: &Keyset,
+        items_per_page: i64,
+    ) -> DBResult<Option<Vec<MovieShort>>>;
+
+    #[allow(clippy::too_many_arguments)]
+    async fn q_get_movies_short_by_cinema(
+        &self,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        cinema_id: i32,
+        badge: Option<Badge>,
+        sort: MovieSort,
+        keyset: &Keyset,
+        items_per_page: i64,
+    ) -> DBResult<Option<Vec<MovieShort>>>;
+
+    async fn q_count_movies(&self, date: NaiveDate, now: DateTime<Utc>, badge: Option<Badge>) -> DBResult<i64>;
+
+    // the movies found by the title
+    #[allow(clippy::too_many_arguments)]
+    async fn q_search_movies(
+        &self,
+        text: &str,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+        sort: MovieSort,
+        keyset: &Keyset,
+        items_per_page: i64,
+    ) -> DBResult<Option<Vec<MovieShort>>>;
+
+    async fn q_count_search_movies(&self, text: &str, date: NaiveDate, now: DateTime<Utc>, badge: Option<Badge>) -> DBResult<i64>;
+
+    // the movie already looked up on the sites (even if it wasn't found on some of them), of any year if `year` is None
+    async fn q_get_enriched_movie_id(&self, title: &str, year: Option<i32>) -> DBResult<Option<i32>>;
+
+    // the text searched by the title (the buttons of the found movies carry its id)
+    async fn q_get_search(&self, search_id: i32) -> DBResult<Option<String>>;
+
+    async fn q_get_latest_run(&self) -> DBResult<Option<ScrapeRun>>;
+
+    async fn q_get_latest_run_by_date(&self, date: NaiveDate) -> DBResult<Option<ScrapeRun>>;
+
+    async fn insert_user(&self, id: i64, username: Option<&str>) -> DBResult<()>;
+
+    async fn replace_sessions(
+        &self,
+        cinema_id: i32,
+        showdate: NaiveDate,
+        sessions: &[(i32, Session)],
+        remove_unseen: bool,
+    ) -> DBResult<SessionsReplaced>;
+
+    // sessions of the date in the cinemas the run didn't see are gone from the site
+    async fn remove_sessions_of_unseen_cinemas(&self, showdate: NaiveDate, seen_cinema_ids: &[i32]) -> DBResult<u64>;
+
+    async fn insert_scrape_run(&self, showdate: NaiveDate) -> DBResult<i32>;
+
+    #[allow(clippy::too_many_arguments)]
+    async fn finish_scrape_run(
+        &self,
+        run_id: i32,
+        status: RunStatus,
+        cinemas: i32,
+        movies: i32,
+        sessions: i32,
+        errors: i32,
+    ) -> DBResult<()>;
+
+    async fn insert_cinema(&self, cinema: &Cinema) -> DBResult<i32>;
+
+    async fn insert_movie(&self, movie: &Movie) -> DBResult<i32>;
+
+    async fn set_movie_enriched(&self, movie_id: i32) -> DBResult<()>;
+
+    async fn insert_search(&self, text: &str) -> DBResult<i32>;
+}
+
+// the dialogues (open menus) of the bot chats as json, a dialogue not updated for `ttl` is treated as absent
+#[async_trait]
+pub trait DialogueRepository: Send + Sync {
+    async fn q_get_dialogue(&self, chat_id: i64, ttl: Duration) -> DBResult<Option<serde_json::Value>>;
+
+    async fn upsert_dialogue(&self, chat_id: i64, state: &serde_json::Value) -> DBResult<()>;
+
+    async fn delete_dialogue(&self, chat_id: i64) -> DBResult<()>;
+
+    // returns how many were deleted
+    async fn delete_stale_dialogues(&self, ttl: Duration) -> DBResult<u64>;
+}
+
+#[async_trait]
+impl ScheduleRepository for DB {
+    fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    async fn q_get_sessions_by_cinema(
+        &self,
+        movie_id: i32,
+        cinema_id: i32,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+    ) -> DBResult<Option<Vec<Session>>> {
+        DB::q_get_sessions_by_cinema(&self.conn, movie_id, cinema_id, date, now, badge).await
+    }
+
+    async fn q_get_sessions_all(
+        &self,
+        movie_id: i32,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+    ) -> DBResult<Option<Vec<Session>>> {
+        DB::q_get_sessions_all(&self.conn, movie_id, date, now, badge).await
+    }
+
+    async fn q_get_sessions_of_cinema(
+        &self,
+        cinema_id: i32,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+    ) -> DBResult<Option<Vec<MovieSession>>> {
+        DB::q_get_sessions_of_cinema(&self.conn, cinema_id, date, now, badge).await
+    }
+
+    async fn q_get_cinemas(&self) -> DBResult<Option<Vec<Cinema>>> {
+        DB::q_get_cinemas(&self.conn).await
+    }
+
+    async fn q_get_cinema_by_id(&self, cinema_id: i32) -> DBResult<Option<Cinema>> {
+        DB::q_get_cinema_by_id(&self.conn, cinema_id).await
+    }
+
+    async fn q_count_movies_by_cinema(&self, date: NaiveDate, now: DateTime<Utc>, cinema_id: i32, badge: Option<Badge>) -> DBResult<i64> {
+        DB::q_count_movies_by_cinema(&self.conn, date, now, cinema_id, badge).await
+    }
+
+    async fn q_get_movie_by_id(&self, movie_id: i32) -> DBResult<Option<Movie>> {
+        DB::q_get_movie_by_id(&self.conn, movie_id).await
+    }
+
+    async fn q_get_movies_short(
+        &self,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+        sort: MovieSort,
+        keyset: &Keyset,
+        items_per_page: i64,
+    ) -> DBResult<Option<Vec<MovieShort>>> {
+        DB::q_get_movies_short(&self.conn, date, now, badge, sort, keyset, items_per_page).await
+    }
+
+    async fn q_get_movies_short_by_cinema(
+        &self,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        cinema_id: i32,
+        badge: Option<Badge>,
+        sort: MovieSort,
+        keyset: &Keyset,
+        items_per_page: i64,
+    ) -> DBResult<Option<Vec<MovieShort>>> {
+        DB::q_get_movies_short_by_cinema(&self.conn, date, now, cinema_id, badge, sort, keyset, items_per_page).await
+    }
+
+    async fn q_count_movies(&self, date: NaiveDate, now: DateTime<Utc>, badge: Option<Badge>) -> DBResult<i64> {
+        DB::q_count_movies(&self.conn, date, now, badge).await
+    }
+
+    async fn q_search_movies(
+        &self,
+        text: &str,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+        badge: Option<Badge>,
+        sort: MovieSort,
+        keyset: &Keyset,
+        items_per_page: i64,
+    ) -> DBResult<Option<Vec<MovieShort>>> {
+        DB::q_search_movies(&self.conn, text, date, now, badge, sort, keyset, items_per_page).await
+    }
+
+    async fn q_count_search_movies(&self, text: &str, date: NaiveDate, now: DateTime<Utc>, badge: Option<Badge>) -> DBResult<i64> {
+        DB::q_count_search_movies(&self.conn, text, date, now, badge).await
+    }
+
+    async fn q_get_enriched_movie_id(&self, title: &str, year: Option<i32>) -> DBResult<Option<i32>> {
+        DB::q_get_enriched_movie_id(&self.conn, title, year).await
+    }
+
+    async fn q_get_search(&self, search_id: i32) -> DBResult<Option<String>> {
+        DB::q_get_search(&self.conn, search_id).await
+    }
+
+    async fn q_get_latest_run(&self) -> DBResult<Option<ScrapeRun>> {
+        DB::q_get_latest_run(&self.conn).await
+    }
+
+    async fn q_get_latest_run_by_date(&self, date: NaiveDate) -> DBResult<Option<ScrapeRun>> {
+        DB::q_get_latest_run_by_date(&self.conn, date).await
+    }
+
+    async fn insert_user(&self, id: i64, username: Option<&str>) -> DBResult<()> {
+        DB::insert_user(self, id, username).await.map(|_| ())
+    }
+
+    async fn replace_sessions(
+        &self,
+        cinema_id: i32,
+        showdate: NaiveDate,
+        sessions: &[(i32, Session)],
+        remove_unseen: bool,
+    ) -> DBResult<SessionsReplaced> {
+        DB::replace_sessions(self, cinema_id, showdate, sessions, remove_unseen).await
+    }
+
+    async fn remove_sessions_of_unseen_cinemas(&self, showdate: NaiveDate, seen_cinema_ids: &[i32]) -> DBResult<u64> {
+        DB::remove_sessions_of_unseen_cinemas(self, showdate, seen_cinema_ids).await
+    }
+
+    async fn insert_scrape_run(&self, showdate: NaiveDate) -> DBResult<i32> {
+        DB::insert_scrape_run(self, showdate).await
+    }
+
+    async fn finish_scrape_run(
+        &self,
+        run_id: i32,
+        status: RunStatus,
+        cinemas: i32,
+        movies: i32,
+        sessions: i32,
+        errors: i32,
+    ) -> DBResult<()> {
+        DB::finish_scrape_run(self, run_id, status, cinemas, movies, sessions, errors)
+            .await
+            .map(|_| ())
+    }
+
+    async fn insert_cinema(&self, cinema: &Cinema) -> DBResult<i32> {
+        DB::insert_cinema(self, cinema).await
+    }
+
+    async fn insert_movie(&self, movie: &Movie) -> DBResult<i32> {
+        DB::insert_movie(self, movie).await
+    }
+
+    async fn set_movie_enriched(&self, movie_id: i32) -> DBResult<()> {
+        DB::set_movie_enriched(self, movie_id).await
+    }
+
+    async fn insert_search(&self, text: &str) -> DBResult<i32> {
+        DB::insert_search(self, text).await
+    }
+}
+
+#[async_trait]
+impl DialogueRepository for DB {
+    async fn q_get_dialogue(&self, chat_id: i64, ttl: Duration) -> DBResult<Option<serde_json::Value>> {
+        DB::q_get_dialogue(self, chat_id, ttl).await
+    }
+
+    async fn upsert_dialogue(&self, chat_id: i64, state: &serde_json::Value) -> DBResult<()> {
+        DB::upsert_dialogue(self, chat_id, state).await
+    }
+
+    async fn delete_dialogue(&self, chat_id: i64) -> DBResult<()> {
+        DB::delete_dialogue(self, chat_id).await
+    }
+
+    async fn delete_stale_dialogues(&self, ttl: Duration) -> DBResult<u64> {
+        DB::delete_stale_dialogues(self, ttl).await
+    }
+}
@@ -1,18 +1,57 @@
-use chrono::{Duration, NaiveDate, NaiveTime, Utc};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
 
-pub fn datetime_utc3() -> (NaiveDate, NaiveTime) {
-    let d = Utc::now() + Duration::hours(3);
-    (d.date_naive(), d.time())
-}
+use super::clock::MOSCOW;
 
-// if the transmitted date is greater than the current one,
-// then you need to make a sample for the entire day, i.e. from 00:00 hours
-pub(super) fn time_determine(date: NaiveDate) -> NaiveTime {
-    let (curr_date, curr_time) = datetime_utc3();
+// the cinema day starts at 06:00: the sessions after midnight belong to the previous date of the schedule
+pub const CINEMA_DAY_START: NaiveTime = match NaiveTime::from_hms_opt(6, 0, 0) {
+    Some(time) => time,
+    None => panic!(),
+};
 
-    if date > curr_date {
-        NaiveTime::from_hms_opt(0, 0, 0).unwrap()
+// the date of the schedule the moment belongs to, e.g. 00:30 of the 24th is still the 23rd
+pub fn cinema_date(date: NaiveDate, time: NaiveTime) -> NaiveDate {
+    if time < CINEMA_DAY_START {
+        date.pred_opt().unwrap_or(date)
     } else {
-        curr_time
+        date
+    }
+}
+
+// when a session of the schedule date starts, 00:30 is the night after the date
+pub fn session_start(date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+    let date = if time < CINEMA_DAY_START { date + Duration::days(1) } else { date };
+
+    let local = date.and_time(time);
+
+    // a time skipped by a clock change is moved forward as the clocks were
+    MOSCOW
+        .from_local_datetime(&local)
+        .earliest()
+        .or_else(|| MOSCOW.from_local_datetime(&(local + Duration::hours(1))).earliest())
+        .map(|start| start.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&local))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cinema_day() {
+        let date = NaiveDate::from_ymd_opt(2023, 12, 23).unwrap();
+        let time = |s| NaiveTime::parse_from_str(s, "%H:%M").unwrap();
+
+        assert_eq!(cinema_date(date + Duration::days(1), time("00:30")), date);
+        assert_eq!(cinema_date(date, time("06:00")), date);
+
+        assert_eq!(
+            session_start(date, time("13:00")),
+            Utc.with_ymd_and_hms(2023, 12, 23, 10, 0, 0).unwrap()
+        );
+        // after midnight
+        assert_eq!(
+            session_start(date, time("00:30")),
+            Utc.with_ymd_and_hms(2023, 12, 23, 21, 30, 0).unwrap()
+        );
     }
 }
@@ -0,0 +1,34 @@
+-- the schedule of 2023-12-23 (the ids don't clash with the serial ones)
+INSERT INTO moskino.cinemas (cinema_id, name, address, metro, metro_color, is_active) VALUES
+    (100, 'Сатурн', 'Снежная ул., д. 18', 'Свиблово', '#EF8532', true),
+    (101, 'Березка', 'Пр-т Мира, д. 95', NULL, NULL, true),
+    -- closed, the parser doesn't see it anymore
+    (102, 'Ладога', NULL, NULL, NULL, false);
+
+INSERT INTO moskino.movies (movie_id, title, year, genre, director, description, href_moskino, href_kinopoisk) VALUES
+    (100, 'Бешеные псы', 1991, 'криминал', 'Квентин Тарантино', 'Ограбление пошло не по плану.',
+        'https://mos-kino.ru/film/reservoir-dogs/', 'https://www.kinopoisk.ru/film/394/'),
+    (101, 'Олдбой', 2003, 'триллер', 'Пак Чхан-ук', NULL, 'https://mos-kino.ru/film/oldboy/', NULL),
+    (102, 'По щучьему велению', 2023, 'сказка', NULL, NULL, NULL, NULL),
+    (103, 'Следующая жертва', 2023, NULL, NULL, NULL, NULL, NULL),
+    (104, 'Елки 10', 0, NULL, NULL, NULL, NULL, NULL);
+
+UPDATE moskino.movies SET enriched_at = '2023-12-22 03:00+03' WHERE movie_id = 100;
+
+INSERT INTO moskino.sessions (moskino_session_id, cinema_id, movie_id, showdate, starts_at, price, price_kind, badges) VALUES
+    (1001, 100, 102, '2023-12-23', '2023-12-23 11:00+03', 0, 'free', '{2d,pushkin}'),
+    (1002, 100, 103, '2023-12-23', '2023-12-23 13:00+03', 250, 'paid', '{2d}'),
+    (1003, 100, 100, '2023-12-23', '2023-12-23 21:30+03', 300, 'paid', '{2d,subtitles}'),
+    -- after midnight, still the cinema day of the 23rd
+    (1004, 100, 100, '2023-12-23', '2023-12-24 00:30+03', 300, 'paid', '{2d,original}'),
+    (1005, 101, 101, '2023-12-23', '2023-12-23 20:00+03', 0, 'invitation', '{2d,subtitles}'),
+    (1006, 101, 102, '2023-12-23', '2023-12-23 15:00+03', 200, 'paid', '{3d}'),
+    (1007, 101, 104, '2023-12-23', '2023-12-23 17:00+03', 200, 'paid', '{2d}'),
+    -- the next day
+    (1008, 101, 101, '2023-12-24', '2023-12-24 19:00+03', 250, 'paid', '{2d}');
+
+INSERT INTO moskino.scrape_runs (showdate, started_at, finished_at, status, cinemas, movies, sessions, errors) VALUES
+    ('2023-12-23', '2023-12-23 07:00+03', '2023-12-23 07:05+03', 'ok', 2, 5, 7, 0),
+    ('2023-12-23', '2023-12-23 12:00+03', '2023-12-23 12:04+03', 'partial', 2, 5, 7, 1),
+    ('2023-12-23', '2023-12-23 18:00+03', NULL, 'running', 0, 0, 0, 0),
+    ('2023-12-24', '2023-12-23 23:00+03', '2023-12-23 23:03+03', 'failed', 0, 0, 0, 1);
@@ -0,0 +1,547 @@
+// queries against a real postgres: every test gets its own throwaway database
+// with db/migrations and the seeds of tests/fixtures applied (dropped after a successful run)
+//
+// DATABASE_URL=postgres://postgres@localhost/moskino_dev cargo test -p db -- --ignored
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use db::{
+    clock::{FixedClock, MOSCOW},
+    tools::session_start,
+    Badge, Cinema, Error, Keyset, Movie, MovieCursor, MovieSort, PriceKind, RunStatus, Session, SessionsReplaced, DB,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+fn date() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2023, 12, 23).unwrap()
+}
+
+// moscow time of the 23rd
+fn msk(hour: u32, min: u32) -> DateTime<Utc> {
+    MOSCOW.with_ymd_and_hms(2023, 12, 23, hour, min, 0).unwrap().with_timezone(&Utc)
+}
+
+fn ids(sessions: Option<Vec<Session>>) -> Vec<i64> {
+    sessions.unwrap_or_default().iter().map(|s| s.moskino_session_id).collect()
+}
+
+#[sqlx::test(migrations = "./migrations")]
+#[ignore = "needs postgres (DATABASE_URL)"]
+async fn test_q_get_movie_by_id(pool: PgPool) {
+    // seeded by the first migration
+    let movie_test = Movie {
+        id: 1,
+        title: "Test Movie".to_string(),
+        year: Some(2023),
+        genre: Some("Action".to_string()),
+        director: Some("Test Director".to_string()),
+        description: Some("This is a description of the test movie.".to_string()),
+        href_moskino: Some("http://example.com/moskino/test-movie".to_string()),
+        href_kinopoisk: Some("http://example.com/kinopoisk/test-movie".to_string()),
+        country: Some("United States".to_string()),
+        duration: Some(120),
+        age: Some(16),
+        tagline: Some("An exciting test movie".to_string()),
+    };
+
+    let movie = DB::q_get_movie_by_id(&pool, 1).await.unwrap().unwrap();
+
+    assert_eq!(movie, movie_test);
+}
+
+#[sqlx::test(migrations = "./migrations", fixtures("schedule"))]
+#[ignore = "needs postgres (DATABASE_URL)"]
+async fn test_movies_short_pagination(pool: PgPool) {
+    let now = msk(6, 0);
+    let page = |keyset: Keyset| {
+        let pool = pool.clone();
+        async move {
+            DB::q_get_movies_short(&pool, date(), now, None, MovieSort::Title, &keyset, 2)
+                .await
+                .unwrap()
+                .map(|movies| movies.iter().map(|movie| movie.id).collect::<Vec<i32>>())
+        }
+    };
+
+    assert_eq!(DB::q_count_movies(&pool, date(), now, None).await.unwrap(), 5);
+
+    // Бешеные псы, Елки 10 | Олдбой, По щучьему велению | Следующая жертва
+    assert_eq!(page(Keyset::First).await.unwrap(), vec![100, 104]);
+    let elki = movie_cursor(&pool, 104, MovieSort::Title).await;
+    assert_eq!(page(Keyset::After(elki.clone())).await.unwrap(), vec![101, 102]);
+
+    // a movie added while paging doesn't shift the next pages
+    sqlx::query("INSERT INTO moskino.movies (movie_id, title, year) VALUES (105, 'Аватар', 2009);")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO moskino.sessions (moskino_session_id, cinema_id, movie_id, showdate, starts_at, price) VALUES (1009, 100, 105, '2023-12-23', '2023-12-23 19:00+03', 300);")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let pike = movie_cursor(&pool, 102, MovieSort::Title).await;
+    assert_eq!(page(Keyset::After(pike)).await.unwrap(), vec![103]);
+    assert_eq!(page(Keyset::Before(elki)).await.unwrap(), vec![105, 100]);
+
+    // behind the last page
+    let victim = movie_cursor(&pool, 103, MovieSort::Title).await;
+    assert!(page(Keyset::After(victim)).await.is_none());
+    // another day
+    let date = NaiveDate::from_ymd_opt(2023, 12, 24).unwrap();
+    assert_eq!(DB::q_count_movies(&pool, date, now, None).await.unwrap(), 1);
+}
+
+async fn movie_cursor(pool: &PgPool, movie_id: i32, sort: MovieSort) -> MovieCursor {
+    DB::q_get_movies_short(pool, date(), msk(6, 0), None, sort, &Keyset::First, 100)
+        .await
+        .unwrap()
+        .unwrap()
+        .iter()
+        .find(|movie| movie.id == movie_id)
+        .unwrap()
+        .cursor()
+}
+
+#[sqlx::test(migrations = "./migrations", fixtures("schedule"))]
+#[ignore = "needs postgres (DATABASE_URL)"]
+async fn test_movies_sort(pool: PgPool) {
+    let sorted = |sort: MovieSort| {
+        let pool = pool.clone();
+        async move {
+            let movies = DB::q_get_movies_short(&pool, date(), msk(6, 0), None, sort, &Keyset::First, 10)
+                .await
+                .unwrap()
+                .unwrap();
+            movies.iter().map(|movie| movie.id).collect::<Vec<i32>>()
+        }
+    };
+
+    assert_eq!(sorted(MovieSort::Title).await, vec![100, 104, 101, 102, 103]);
+    assert_eq!(sorted(MovieSort::Showtime).await, vec![102, 103, 104, 101, 100]);
+    // ties are sorted by the title
+    assert_eq!(sorted(MovieSort::Sessions).await, vec![100, 102, 104, 101, 103]);
+    // the year is unknown for Елки 10
+    assert_eq!(sorted(MovieSort::Release).await, vec![102, 103, 101, 100, 104]);
+
+    // in Березка every movie has one session, so they go by the title, the page is taken backwards
+    let cursor = MovieCursor {
+        key: -1,
+        title: "По щучьему велению".to_string(),
+        id: 102,
+    };
+    let movies = DB::q_get_movies_short_by_cinema(&pool, date(), msk(6, 0), 101, None, MovieSort::Sessions, &Keyset::Before(cursor), 1)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(movies[0].id, 101);
+    assert_eq!(movies[0].cursor().key, -1);
+}
+
+#[sqlx::test(migrations = "./migrations", fixtures("schedule"))]
+#[ignore = "needs postgres (DATABASE_URL)"]
+async fn test_search_movies(pool: PgPool) {
+    let found = |text: &'static str| {
+        let pool = pool.clone();
+        async move {
+            DB::q_search_movies(&pool, text, date(), msk(6, 0), None, MovieSort::Relevance, &Keyset::First, 10)
+                .await
+                .unwrap()
+                .map(|movies| movies.iter().map(|movie| movie.id).collect::<Vec<i32>>())
+        }
+    };
+
+    // ё is е, typos and word forms
+    sqlx::query("UPDATE moskino.movies SET title = 'Ёлки 10' WHERE movie_id = 104;")
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(found("елки").await.unwrap(), vec![104]);
+    assert_eq!(found("олдбй").await.unwrap(), vec![101]);
+    assert_eq!(found("бешенные").await.unwrap(), vec![100]);
+    assert_eq!(found("жертвы").await.unwrap(), vec![103]);
+    assert!(found("терминатор").await.is_none());
+
+    assert_eq!(
+        DB::q_count_search_movies(&pool, "олдбой", date(), msk(6, 0), None).await.unwrap(),
+        1
+    );
+    // no 3D sessions
+    assert_eq!(
+        DB::q_count_search_movies(&pool, "олдбой", date(), msk(6, 0), Some(Badge::ThreeD))
+            .await
+            .unwrap(),
+        0
+    );
+
+    // equally similar ones go by the title
+    sqlx::query("INSERT INTO moskino.movies (movie_id, title, year) VALUES (105, 'Олдбой 2', 2013);")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO moskino.sessions (moskino_session_id, cinema_id, movie_id, showdate, starts_at, price) VALUES (1009, 100, 105, '2023-12-23', '2023-12-23 19:00+03', 300);")
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(found("олдбой").await.unwrap(), vec![101, 105]);
+}
+
+#[sqlx::test(migrations = "./migrations", fixtures("schedule"))]
+#[ignore = "needs postgres (DATABASE_URL)"]
+async fn test_upcoming_cut_off(pool: PgPool) {
+    // the session at 13:00 is just gone
+    let now = msk(13, 1);
+
+    let sessions = DB::q_get_sessions_by_cinema(&pool, 103, 100, date(), now, None).await.unwrap();
+    assert!(sessions.is_none());
+    let sessions = DB::q_get_sessions_by_cinema(&pool, 103, 100, date(), msk(13, 0), None)
+        .await
+        .unwrap();
+    assert_eq!(ids(sessions), vec![1002]);
+
+    // the session after midnight goes last
+    let sessions = DB::q_get_sessions_all(&pool, 100, date(), now, None).await.unwrap();
+    assert_eq!(ids(sessions), vec![1003, 1004]);
+    let sessions = DB::q_get_sessions_all(&pool, 100, date(), msk(23, 0), None).await.unwrap().unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].starts_at, session_start(date(), "00:30".parse().unwrap()));
+    assert_eq!(sessions[0].showdate, date());
+
+    // По щучьему велению at 11:00 and Следующая жертва at 13:00 are over
+    assert_eq!(DB::q_count_movies(&pool, date(), now, None).await.unwrap(), 4);
+    assert_eq!(DB::q_count_movies(&pool, date(), msk(22, 0), None).await.unwrap(), 1);
+    let movies = DB::q_get_movies_short(&pool, date(), msk(22, 0), None, MovieSort::Title, &Keyset::First, 10)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(movies[0].title, "Бешеные псы");
+}
+
+#[sqlx::test(migrations = "./migrations", fixtures("schedule"))]
+#[ignore = "needs postgres (DATABASE_URL)"]
+async fn test_cinema_filtering(pool: PgPool) {
+    let now = msk(6, 0);
+
+    // the closed cinema is hidden
+    let mut cinemas: Vec<i32> = DB::q_get_cinemas(&pool).await.unwrap().unwrap().iter().map(|c| c.id).collect();
+    cinemas.sort();
+    assert_eq!(cinemas, vec![100, 101]);
+    assert!(DB::q_get_cinema_by_id(&pool, 102).await.unwrap().is_none());
+    let cinema = DB::q_get_cinema_by_id(&pool, 100).await.unwrap().unwrap();
+    assert_eq!(cinema.location().unwrap(), "Снежная ул., д. 18 • 🟠 Свиблово");
+
+    assert_eq!(DB::q_count_movies_by_cinema(&pool, date(), now, 100, None).await.unwrap(), 3);
+    assert_eq!(DB::q_count_movies_by_cinema(&pool, date(), now, 101, None).await.unwrap(), 3);
+    assert_eq!(DB::q_count_movies_by_cinema(&pool, date(), now, 102, None).await.unwrap(), 0);
+
+    let mut movies: Vec<i32> = DB::q_get_movies_short_by_cinema(&pool, date(), now, 101, None, MovieSort::Title, &Keyset::First, 10)
+        .await
+        .unwrap()
+        .unwrap()
+        .iter()
+        .map(|movie| movie.id)
+        .collect();
+    movies.sort();
+    assert_eq!(movies, vec![101, 102, 104]);
+
+    // По щучьему велению is on in both cinemas
+    let sessions = DB::q_get_sessions_by_cinema(&pool, 102, 101, date(), now, None).await.unwrap();
+    assert_eq!(ids(sessions), vec![1006]);
+    let sessions = DB::q_get_sessions_all(&pool, 102, date(), now, None).await.unwrap().unwrap();
+    let names: Vec<&str> = sessions.iter().map(|s| s.cinema_name.as_str()).collect();
+    assert_eq!(names, vec!["Березка", "Сатурн"]);
+    assert_eq!(sessions[1].price_kind, PriceKind::Free);
+    assert_eq!(sessions[1].badges, vec![Badge::TwoD, Badge::Pushkin]);
+
+    // the whole schedule of the cinema in one query
+    let sessions = DB::q_get_sessions_of_cinema(&pool, 101, date(), now, None).await.unwrap().unwrap();
+    let titles: Vec<(&str, i64)> = sessions.iter().map(|s| (s.title.as_str(), s.session.moskino_session_id)).collect();
+    assert_eq!(titles, vec![("По щучьему велению", 1006), ("Елки 10", 1007), ("Олдбой", 1005)]);
+    assert!(DB::q_get_sessions_of_cinema(&pool, 102, date(), now, None).await.unwrap().is_none());
+}
+
+#[sqlx::test(migrations = "./migrations", fixtures("schedule"))]
+#[ignore = "needs postgres (DATABASE_URL)"]
+async fn test_badge_filter(pool: PgPool) {
+    let now = msk(6, 0);
+
+    assert_eq!(DB::q_count_movies(&pool, date(), now, Some(Badge::Subtitles)).await.unwrap(), 2);
+    assert_eq!(DB::q_count_movies(&pool, date(), now, Some(Badge::ThreeD)).await.unwrap(), 1);
+    assert_eq!(
+        DB::q_count_movies_by_cinema(&pool, date(), now, 100, Some(Badge::ThreeD))
+            .await
+            .unwrap(),
+        0
+    );
+
+    let movies = DB::q_get_movies_short(&pool, date(), now, Some(Badge::Pushkin), MovieSort::Title, &Keyset::First, 10)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(movies.len(), 1);
+    assert_eq!(movies[0].id, 102);
+
+    let sessions = DB::q_get_sessions_all(&pool, 100, date(), now, Some(Badge::Original))
+        .await
+        .unwrap();
+    assert_eq!(ids(sessions), vec![1004]);
+    let sessions = DB::q_get_sessions_by_cinema(&pool, 100, 100, date(), now, Some(Badge::ThreeD))
+        .await
+        .unwrap();
+    assert!(sessions.is_none());
+}
+
+#[sqlx::test(migrations = "./migrations", fixtures("schedule"))]
+#[ignore = "needs postgres (DATABASE_URL)"]
+async fn test_upserts(pool: PgPool) {
+    let db = DB::from_pool(pool);
+
+    // the known contacts are kept, the cinema is open again
+    let cinema = Cinema {
+        name: "Ладога".to_string(),
+        metro: Some("Выхино".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(db.insert_cinema(&cinema).await.unwrap(), 102);
+    let cinema = Cinema {
+        name: "Сатурн".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(db.insert_cinema(&cinema).await.unwrap(), 100);
+    let saturn = DB::q_get_cinema_by_id(&db.conn, 100).await.unwrap().unwrap();
+    assert_eq!(saturn.metro.as_deref(), Some("Свиблово"));
+    let ladoga = DB::q_get_cinema_by_id(&db.conn, 102).await.unwrap().unwrap();
+    assert_eq!(ladoga.metro.as_deref(), Some("Выхино"));
+
+    // a movie without the year is stored with 0
+    let movie = Movie {
+        title: "Елки 10".to_string(),
+        genre: Some("комедия".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(db.insert_movie(&movie).await.unwrap(), 104);
+    assert_eq!(
+        DB::q_get_movie_by_id(&db.conn, 104).await.unwrap().unwrap().genre.as_deref(),
+        Some("комедия")
+    );
+    let movie = Movie {
+        title: "Олдбой".to_string(),
+        year: Some(2003),
+        href_kinopoisk: Some("https://www.kinopoisk.ru/film/75871/".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(db.insert_movie(&movie).await.unwrap(), 101);
+    let oldboy = DB::q_get_movie_by_id(&db.conn, 101).await.unwrap().unwrap();
+    assert_eq!(oldboy.director.as_deref(), Some("Пак Чхан-ук"));
+    assert_eq!(oldboy.href_moskino.as_deref(), Some("https://mos-kino.ru/film/oldboy/"));
+
+    // enriched once looked up on the sites, whatever was found
+    assert_eq!(
+        DB::q_get_enriched_movie_id(&db.conn, "Бешеные псы", Some(1991)).await.unwrap(),
+        Some(100)
+    );
+    assert_eq!(DB::q_get_enriched_movie_id(&db.conn, "Олдбой", Some(2003)).await.unwrap(), None);
+    assert_eq!(
+        DB::q_get_enriched_movie_id(&db.conn, "Бешеные псы", Some(2023)).await.unwrap(),
+        None
+    );
+    // the schedule has no year
+    assert_eq!(DB::q_get_enriched_movie_id(&db.conn, "Бешеные псы", None).await.unwrap(), Some(100));
+    db.set_movie_enriched(102).await.unwrap();
+    assert_eq!(
+        DB::q_get_enriched_movie_id(&db.conn, "По щучьему велению", Some(2023))
+            .await
+            .unwrap(),
+        Some(102)
+    );
+
+    // Сатурн: 1002 is rescheduled to 14:00, 1001 and 1004 are cancelled, 1010 is new
+    let session = |id: i64, time: &str| Session {
+        moskino_session_id: id,
+        cinema_name: "Сатурн".to_string(),
+        starts_at: session_start(date(), time.parse().unwrap()),
+        showdate: date(),
+        price: 250,
+        price_kind: PriceKind::Paid,
+        badges: vec![Badge::TwoD],
+    };
+    let sessions = [
+        (103, session(1002, "14:00")),
+        (100, session(1003, "21:30")),
+        (101, session(1010, "23:00")),
+    ];
+    let replaced = db.replace_sessions(100, date(), &sessions, true).await.unwrap();
+    assert_eq!(
+        replaced,
+        SessionsReplaced {
+            inserted: 1,
+            existing: 2,
+            removed: 2
+        }
+    );
+
+    let now = msk(6, 0);
+    let sessions = DB::q_get_sessions_by_cinema(&db.conn, 103, 100, date(), now, None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(sessions[0].showtime(), "14:00".parse().unwrap());
+    let sessions = DB::q_get_sessions_all(&db.conn, 100, date(), now, None).await.unwrap();
+    assert_eq!(ids(sessions), vec![1003]);
+    // Березка is untouched
+    assert_eq!(DB::q_count_movies_by_cinema(&db.conn, date(), now, 101, None).await.unwrap(), 3);
+
+    // a partial scrape keeps the rest of the day
+    let replaced = db
+        .replace_sessions(100, date(), &[(102, session(1011, "18:00"))], false)
+        .await
+        .unwrap();
+    assert_eq!(replaced.removed, 0);
+    assert_eq!(DB::q_count_movies_by_cinema(&db.conn, date(), now, 100, None).await.unwrap(), 4);
+
+    // Березка is gone from the schedule of the day, the next day is kept
+    assert_eq!(db.remove_sessions_of_unseen_cinemas(date(), &[100]).await.unwrap(), 3);
+    assert_eq!(DB::q_count_movies_by_cinema(&db.conn, date(), now, 101, None).await.unwrap(), 0);
+    assert_eq!(DB::q_count_movies_by_cinema(&db.conn, date(), now, 100, None).await.unwrap(), 4);
+    let next_day = date().succ_opt().unwrap();
+    assert_eq!(DB::q_count_movies_by_cinema(&db.conn, next_day, now, 101, None).await.unwrap(), 1);
+}
+
+#[sqlx::test(migrations = "./migrations", fixtures("schedule"))]
+#[ignore = "needs postgres (DATABASE_URL)"]
+async fn test_sessions_without_moskino_id(pool: PgPool) {
+    let db = DB::from_pool(pool);
+    let now = msk(6, 0);
+
+    // stored before the moskino ids
+    sqlx::query("ALTER TABLE moskino.sessions DROP CONSTRAINT sessions_moskino_session_id_required")
+        .execute(&db.conn)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO moskino.sessions (cinema_id, movie_id, showdate, starts_at, price) VALUES
+            (100, 101, '2023-12-23', '2023-12-23 19:00+03', 300),
+            (100, 101, '2023-12-23', '2023-12-23 22:00+03', 300)",
+    )
+    .execute(&db.conn)
+    .await
+    .unwrap();
+
+    // still shown, without the id
+    let sessions = DB::q_get_sessions_by_cinema(&db.conn, 101, 100, date(), now, None).await.unwrap();
+    assert_eq!(ids(sessions), vec![0, 0]);
+
+    // the scraped session gets its id, the one absent on the site is removed
+    let session = Session {
+        moskino_session_id: 1020,
+        cinema_name: "Сатурн".to_string(),
+        starts_at: session_start(date(), "19:00".parse().unwrap()),
+        showdate: date(),
+        price: 300,
+        price_kind: PriceKind::Paid,
+        badges: vec![Badge::TwoD],
+    };
+    let sessions = vec![(101, session)];
+    let replaced = db.replace_sessions(100, date(), &sessions, true).await.unwrap();
+    assert_eq!((replaced.inserted, replaced.existing), (0, 1));
+    let sessions = DB::q_get_sessions_by_cinema(&db.conn, 101, 100, date(), now, None).await.unwrap();
+    assert_eq!(ids(sessions), vec![1020]);
+}
+
+#[sqlx::test(migrations = "./migrations", fixtures("schedule"))]
+#[ignore = "needs postgres (DATABASE_URL)"]
+async fn test_scrape_runs(pool: PgPool) {
+    // the running and the failed runs don't update the schedule
+    let run = DB::q_get_latest_run_by_date(&pool, date()).await.unwrap().unwrap();
+    assert_eq!(run.status, RunStatus::Partial);
+    assert_eq!(run.finished_at, Some(msk(12, 4)));
+    assert!(DB::q_get_latest_run_by_date(&pool, date().succ_opt().unwrap())
+        .await
+        .unwrap()
+        .is_none());
+
+    let run = DB::q_get_latest_run(&pool).await.unwrap().unwrap();
+    assert_eq!(run.status, RunStatus::Failed);
+
+    let db = DB::from_pool(pool);
+    let run_id = db.insert_scrape_run(date()).await.unwrap();
+    db.finish_scrape_run(run_id, RunStatus::Ok, 2, 5, 8, 0).await.unwrap();
+    let run = DB::q_get_latest_run_by_date(&db.conn, date()).await.unwrap().unwrap();
+    assert_eq!((run.id, run.status, run.sessions), (run_id, RunStatus::Ok, 8));
+}
+
+#[sqlx::test(migrations = "./migrations", fixtures("schedule"))]
+#[ignore = "needs postgres (DATABASE_URL)"]
+async fn test_errors(pool: PgPool) {
+    assert!(DB::q_get_movie_by_id(&pool, 999).await.unwrap().is_none());
+
+    let e: Error = sqlx::query("INSERT INTO moskino.cinemas (name) VALUES ('Сатурн');")
+        .execute(&pool)
+        .await
+        .unwrap_err()
+        .into();
+    assert!(matches!(e, Error::Duplicate { constraint: Some(ref c) } if c == "cinemas_name_key"));
+
+    // the movie is absent
+    let db = DB::from_pool(pool);
+    let session = Session {
+        moskino_session_id: 1020,
+        cinema_name: "Сатурн".to_string(),
+        starts_at: msk(18, 0),
+        showdate: date(),
+        price: 250,
+        price_kind: PriceKind::Paid,
+        badges: vec![],
+    };
+    let e = db.replace_sessions(100, date(), &[(999, session)], true).await.unwrap_err();
+    assert!(matches!(e, Error::Conflict { .. }), "{:?}", e);
+    // the transaction is rolled back
+    assert_eq!(
+        DB::q_count_movies_by_cinema(&db.conn, date(), msk(6, 0), 100, None).await.unwrap(),
+        3
+    );
+}
+
+#[sqlx::test(migrations = "./migrations")]
+#[ignore = "needs postgres (DATABASE_URL)"]
+async fn test_dialogues(pool: PgPool) {
+    let ttl = Duration::hours(48);
+    let state = serde_json::json!({ "StartOption": { "date": "2023-12-23", "badge": null } });
+
+    let db = DB::from_pool(pool.clone()).with_clock(Arc::new(FixedClock(msk(12, 0))));
+    assert!(db.q_get_dialogue(1, ttl).await.unwrap().is_none());
+    db.upsert_dialogue(1, &serde_json::json!("DayOption")).await.unwrap();
+    db.upsert_dialogue(1, &state).await.unwrap();
+    assert_eq!(db.q_get_dialogue(1, ttl).await.unwrap(), Some(state.clone()));
+
+    // two days later the dialogue is stale
+    let db = DB::from_pool(pool.clone()).with_clock(Arc::new(FixedClock(msk(12, 0) + Duration::hours(49))));
+    db.upsert_dialogue(2, &state).await.unwrap();
+    assert!(db.q_get_dialogue(1, ttl).await.unwrap().is_none());
+    assert_eq!(db.delete_stale_dialogues(ttl).await.unwrap(), 1);
+
+    db.delete_dialogue(2).await.unwrap();
+    assert!(db.q_get_dialogue(2, ttl).await.unwrap().is_none());
+}
+
+#[sqlx::test(migrations = "./migrations")]
+#[ignore = "needs postgres (DATABASE_URL)"]
+async fn test_searches(pool: PgPool) {
+    let db = DB::from_pool(pool);
+
+    let id = db
+        .insert_search("Приключения Шерлока Холмса и доктора Ватсона: Собака Баскервилей")
+        .await
+        .unwrap();
+    assert_eq!(
+        db.insert_search("Приключения Шерлока Холмса и доктора Ватсона: Собака Баскервилей")
+            .await
+            .unwrap(),
+        id
+    );
+    assert_ne!(db.insert_search("елки").await.unwrap(), id);
+
+    assert_eq!(
+        DB::q_get_search(&db.conn, id).await.unwrap().as_deref(),
+        Some("Приключения Шерлока Холмса и доктора Ватсона: Собака Баскервилей")
+    );
+    assert!(DB::q_get_search(&db.conn, 0).await.unwrap().is_none());
+}
@@ -1,14 +1,154 @@
-use super::URL_MOSKINO_SCHEDULE;
+use super::daemon::Schedule;
+use super::moskino::throttle::Throttle;
 
-use chrono::{Duration, NaiveDate, Utc};
-use clap::{Parser, ValueEnum};
+use chrono::{NaiveDate, NaiveTime};
+use clap::{Args as ClapArgs, Parser, Subcommand, ValueEnum};
+use db::clock::Clock;
+use std::{path::PathBuf, time::Duration};
+
+// the site publishes about a week ahead, a longer range is a typo
+const MAX_DAYS: usize = 14;
 
 #[derive(Debug, Parser)]
 pub(super) struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     // what day to parse movies
-    #[arg(short, long, default_value = "today")]
+    #[arg(short, long, default_value = "today", conflicts_with_all = ["date", "from", "days_ahead"])]
     #[clap(value_enum)]
     pub day: ArgDay,
+
+    // parse movies of the date
+    #[arg(long, value_name = "YYYY-MM-DD", conflicts_with_all = ["from", "days_ahead"])]
+    pub date: Option<NaiveDate>,
+
+    // parse movies of every date from --from to --to inclusive
+    #[arg(long, value_name = "YYYY-MM-DD", requires = "to", conflicts_with = "days_ahead")]
+    pub from: Option<NaiveDate>,
+
+    #[arg(long, value_name = "YYYY-MM-DD", requires = "from")]
+    pub to: Option<NaiveDate>,
+
+    // parse movies of today and N days after it (up to 13)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(..MAX_DAYS as i64))]
+    pub days_ahead: Option<u32>,
+
+    // read pages saved on disk instead of requesting the sites (see web-parser/fixtures)
+    #[arg(long, value_name = "DIR", conflicts_with = "record")]
+    pub replay: Option<PathBuf>,
+
+    // save every requested page into DIR, so the run can be replayed later
+    #[arg(long, value_name = "DIR")]
+    pub record: Option<PathBuf>,
+
+    // write the run summary as json into FILE
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<PathBuf>,
+
+    // enrich movies again even if they are already stored with full details
+    #[arg(long)]
+    pub refresh: bool,
+
+    // max simultaneous requests to a site
+    #[arg(long, default_value_t = 4)]
+    pub concurrency: usize,
+
+    // overrides --concurrency for a site, e.g. kinopoisk.ru=1 (can be repeated)
+    #[arg(long, value_name = "HOST=N", value_parser = parse_host_limit)]
+    pub host_concurrency: Vec<(String, usize)>,
+
+    // max requests per second to a site (0 - unlimited)
+    #[arg(long, default_value_t = 2.0, value_parser = parse_rps)]
+    pub rps: f64,
+
+    // how many times to repeat a request answered with 429/5xx
+    #[arg(long, default_value_t = 3)]
+    pub retries: u32,
+
+    // delay before the first retry in milliseconds, doubled on every next one
+    #[arg(long, value_name = "MS", default_value_t = 1000)]
+    pub backoff: u64,
+}
+
+impl Args {
+    // the dates to parse, one by one
+    pub(super) fn dates(&self, clock: &dyn Clock) -> Result<Vec<NaiveDate>, String> {
+        match (self.date, self.from, self.to, self.days_ahead) {
+            (Some(date), ..) => Ok(vec![date]),
+            (_, Some(from), Some(to), _) if from > to => Err(format!("--from {} is after --to {}", from, to)),
+            (_, Some(from), Some(to), _) if (to - from).num_days() >= MAX_DAYS as i64 => Err(format!(
+                "--from {} --to {}: at most {} days can be parsed at once",
+                from, to, MAX_DAYS
+            )),
+            (_, Some(from), Some(to), _) => Ok(from.iter_days().take_while(|date| *date <= to).collect()),
+            (.., Some(days_ahead)) => Ok(ArgDay::Today.date(clock).iter_days().take(days_ahead as usize + 1).collect()),
+            _ => Ok(vec![self.day.date(clock)]),
+        }
+    }
+
+    pub(super) fn throttle(&self) -> Throttle {
+        Throttle {
+            concurrency: self.concurrency,
+            host_concurrency: self.host_concurrency.clone(),
+            rps: self.rps,
+            retries: self.retries,
+            backoff: Duration::from_millis(self.backoff),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub(super) enum Command {
+    // keep running and scrape on schedule (moscow time) until SIGTERM
+    Daemon(DaemonArgs),
+}
+
+#[derive(Debug, ClapArgs)]
+pub(super) struct DaemonArgs {
+    // scrape today every MIN minutes
+    #[arg(long, value_name = "MIN", default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..))]
+    pub every: u32,
+
+    // time to scrape the next days
+    #[arg(long, value_name = "HH:MM", default_value = "03:00", value_parser = parse_time)]
+    pub nightly_at: NaiveTime,
+
+    // how many days after today are scraped nightly (up to 13)
+    #[arg(long, value_name = "N", default_value_t = 2, value_parser = clap::value_parser!(u32).range(..MAX_DAYS as i64))]
+    pub nightly_days: u32,
+}
+
+impl DaemonArgs {
+    pub(super) fn schedule(&self) -> Schedule {
+        Schedule {
+            every: chrono::Duration::minutes(self.every as i64),
+            nightly_at: self.nightly_at,
+            nightly_days: self.nightly_days,
+        }
+    }
+}
+
+fn parse_time(arg: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(arg, "%H:%M").map_err(|_| format!("'{}': expected HH:MM", arg))
+}
+
+// a request every 100 seconds at the slowest
+fn parse_rps(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(rps) if rps == 0.0 || (rps.is_finite() && rps >= 0.01) => Ok(rps),
+        _ => Err(format!("'{}': expected 0 (unlimited) or from 0.01 requests per second", arg)),
+    }
+}
+
+fn parse_host_limit(arg: &str) -> Result<(String, usize), String> {
+    match arg.split_once('=') {
+        Some((host, limit)) if !host.is_empty() => match limit.parse::<usize>() {
+            Ok(limit) if limit > 0 => Ok((host.to_string(), limit)),
+            _ => Err(format!("'{}': limit must be a positive number", limit)),
+        },
+        _ => Err(format!("'{}': expected HOST=N", arg)),
+    }
 }
 
 #[derive(Debug, ValueEnum, Clone)]
@@ -19,15 +159,74 @@ pub(super) enum ArgDay {
 }
 
 impl ArgDay {
-    pub(super) fn date(&self) -> NaiveDate {
+    pub(super) fn date(&self, clock: &dyn Clock) -> NaiveDate {
         match self {
-            Self::Today => (Utc::now() + Duration::hours(3)).date_naive(),
-            Self::Tommorow => (Utc::now() + Duration::hours(3) + Duration::days(1)).date_naive(),
-            Self::Aftertommorow => (Utc::now() + Duration::hours(3) + Duration::days(2)).date_naive(),
+            Self::Today => clock.cinema_today(),
+            Self::Tommorow => clock.cinema_today() + chrono::Duration::days(1),
+            Self::Aftertommorow => clock.cinema_today() + chrono::Duration::days(2),
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use db::clock::FixedClock;
+
+    // 00:30 of the 24th in Moscow, still the cinema day of the 23rd
+    fn dates(args: &[&str]) -> Result<Vec<String>, String> {
+        let clock = FixedClock(Utc.with_ymd_and_hms(2023, 12, 23, 21, 30, 0).unwrap());
+        let args = Args::try_parse_from([&["web-parser"], args].concat()).map_err(|e| e.kind().to_string())?;
+        Ok(args.dates(&clock)?.iter().map(|date| date.to_string()).collect())
+    }
+
+    #[test]
+    fn test_dates() {
+        assert_eq!(dates(&[]), Ok(vec!["2023-12-23".to_string()]));
+        assert_eq!(dates(&["--day", "tommorow"]), Ok(vec!["2023-12-24".to_string()]));
+        assert_eq!(dates(&["--date", "2023-12-23"]), Ok(vec!["2023-12-23".to_string()]));
+        assert_eq!(
+            dates(&["--from", "2023-12-30", "--to", "2024-01-01"]),
+            Ok(vec!["2023-12-30".to_string(), "2023-12-31".to_string(), "2024-01-01".to_string()])
+        );
+        assert_eq!(dates(&["--days-ahead", "6"]).unwrap().len(), 7);
+        assert_eq!(dates(&["--days-ahead", "13"]).unwrap().len(), 14);
+        assert_eq!(dates(&["--from", "2023-12-23", "--to", "2024-01-05"]).unwrap().len(), 14);
+
+        assert!(dates(&["--from", "2024-01-01", "--to", "2023-12-30"]).is_err());
+        assert!(dates(&["--from", "2023-12-23", "--to", "2024-01-06"]).is_err());
+        assert!(dates(&["--days-ahead", "14"]).is_err());
+        assert!(dates(&["--days-ahead", "4294967295"]).is_err());
+        assert!(dates(&["--from", "2024-01-01"]).is_err());
+        assert!(dates(&["--date", "2023-12-23", "--days-ahead", "2"]).is_err());
+        assert!(dates(&["--day", "tommorow", "--date", "2023-12-23"]).is_err());
+    }
+
+    #[test]
+    fn test_nightly_days() {
+        let nightly_days = |days: &str| match Args::try_parse_from(["web-parser", "daemon", "--nightly-days", days]) {
+            Ok(Args {
+                command: Some(Command::Daemon(daemon)),
+                ..
+            }) => Ok(daemon.nightly_days),
+            Ok(_) => unreachable!(),
+            Err(e) => Err(e.kind()),
+        };
+
+        assert_eq!(nightly_days("13"), Ok(13));
+        assert!(nightly_days("14").is_err());
+    }
+
+    #[test]
+    fn test_rps() {
+        let rps = |rps: &str| Args::try_parse_from(["web-parser", "--rps", rps]).map(|args| args.rps);
 
-    pub(super) fn url_by_day(&self) -> String {
-        format!("{}?date={}", URL_MOSKINO_SCHEDULE, self.date().format("%Y-%m-%d"))
+        assert_eq!(rps("0").unwrap(), 0.0);
+        assert_eq!(rps("0.5").unwrap(), 0.5);
+        assert!(rps("-1").is_err());
+        assert!(rps("1e-300").is_err());
+        assert!(rps("inf").is_err());
+        assert!(rps("NaN").is_err());
     }
 }
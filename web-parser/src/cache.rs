@@ -0,0 +1,83 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::OnceCell;
+
+// the same movie is shown by many cinemas: (title, year)
+pub(super) type MovieKey = (String, Option<i32>);
+
+// ids of the movies already stored during the current run,
+// so every distinct movie is enriched and inserted only once
+#[derive(Default)]
+pub(super) struct MovieCache {
+    movies: Mutex<HashMap<MovieKey, Arc<OnceCell<i32>>>>,
+}
+
+impl MovieCache {
+    // tasks asking for the same movie wait for the first one to finish,
+    // a failed attempt is not cached and will be repeated by the next task
+    pub(super) async fn get_or_try_init<F, Fut, E>(&self, key: MovieKey, init: F) -> Result<i32, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<i32, E>>,
+    {
+        let cell = {
+            let mut movies = self.movies.lock().unwrap();
+            Arc::clone(movies.entry(key).or_default())
+        };
+
+        cell.get_or_try_init(init).await.copied()
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.movies.lock().unwrap().values().filter(|cell| cell.initialized()).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_init_once() {
+        let cache = Arc::new(MovieCache::default());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let mut handlers = vec![];
+        for _ in 0..8 {
+            let cache = Arc::clone(&cache);
+            let calls = Arc::clone(&calls);
+
+            handlers.push(tokio::spawn(async move {
+                cache
+                    .get_or_try_init(("Олдбой".to_string(), Some(2003)), || async {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        Ok::<i32, ()>(42)
+                    })
+                    .await
+                    .unwrap()
+            }));
+        }
+
+        for task in handlers {
+            assert_eq!(task.await.unwrap(), 42);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failure_is_not_cached() {
+        let cache = MovieCache::default();
+        let key = ("Олдбой".to_string(), Some(2003));
+
+        let failed = cache.get_or_try_init(key.clone(), || async { Err("site is down") }).await;
+        assert!(failed.is_err());
+
+        let id = cache.get_or_try_init(key, || async { Ok::<i32, ()>(7) }).await.unwrap();
+        assert_eq!(id, 7);
+    }
+}
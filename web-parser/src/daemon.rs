@@ -0,0 +1,203 @@
+use super::moskino::fetcher::PageFetcher;
+use super::report::Report;
+use super::{scrape_into, Res};
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use db::{repository::ScheduleRepository, tools::cinema_date, RunStatus};
+use std::sync::Arc;
+use tokio::signal::unix::{signal, Signal, SignalKind};
+
+// when the daemon scrapes the schedule (moscow time)
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    // today is scraped on every tick, ticks are counted from midnight as cron does
+    pub every: Duration,
+    // the next `nightly_days` days are scraped once a day at `nightly_at`
+    pub nightly_at: NaiveTime,
+    pub nightly_days: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Job {
+    Today,
+    Nightly,
+}
+
+impl Schedule {
+    fn next_today(&self, now: NaiveDateTime) -> NaiveDateTime {
+        let midnight = now.date().and_time(NaiveTime::MIN);
+        let every = self.every.num_seconds().max(1);
+        let ticks = (now - midnight).num_seconds() / every + 1;
+
+        midnight + Duration::seconds(ticks * every)
+    }
+
+    fn next_nightly(&self, now: NaiveDateTime) -> NaiveDateTime {
+        let at = now.date().and_time(self.nightly_at);
+
+        if at > now {
+            at
+        } else {
+            at + Duration::days(1)
+        }
+    }
+
+    // the nightly job goes first if both are due at the same time
+    fn next_job(&self, now: NaiveDateTime) -> (Job, NaiveDateTime) {
+        let today = self.next_today(now);
+        let nightly = self.next_nightly(now);
+
+        if nightly <= today {
+            (Job::Nightly, nightly)
+        } else {
+            (Job::Today, today)
+        }
+    }
+
+    fn dates(&self, job: Job, today: NaiveDate) -> Vec<NaiveDate> {
+        match job {
+            Job::Today => vec![today],
+            Job::Nightly => (1..=self.nightly_days).map(|day| today + Duration::days(day as i64)).collect(),
+        }
+    }
+}
+
+// SIGTERM (docker stop) or Ctrl-C
+async fn shutdown(terminate: &mut Signal) {
+    tokio::select! {
+        _ = terminate.recv() => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+}
+
+// scrapes on schedule until shutdown, the runs share the DB pool and the fetcher (with its limits)
+pub async fn run(db: Arc<dyn ScheduleRepository>, fetcher: Arc<dyn PageFetcher>, schedule: Schedule, refresh: bool) -> Res<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    info!("daemon: {:?}", schedule);
+
+    // today is scraped right after the start
+    let mut job = Job::Today;
+
+    loop {
+        // the runs go one by one, so they never overlap,
+        // the ticks missed during a long run are skipped
+        // after midnight today is still the previous cinema day (its late sessions are on its page),
+        // the nightly job scrapes the days after the calendar date
+        let started = db.clock().now_msk().naive_local();
+        let today = match job {
+            Job::Today => cinema_date(started.date(), started.time()),
+            Job::Nightly => started.date(),
+        };
+
+        for date in schedule.dates(job, today) {
+            let run_id = match db.insert_scrape_run(date).await {
+                Ok(run_id) => run_id,
+                // the other dates would fail the same way, the next tick tries again
+                Err(e) if e.is_unavailable() => {
+                    error!("{}: {}", date, e);
+                    break;
+                }
+                Err(e) => {
+                    error!("{}: {}", date, e);
+                    continue;
+                }
+            };
+
+            let report = Arc::new(Report::new(date));
+
+            tokio::select! {
+                res = scrape_into(&db, &fetcher, run_id, date, refresh, Arc::clone(&report)) => {
+                    if let Err(e) = res {
+                        error!("{}: {}", date, e);
+                    }
+                }
+                _ = shutdown(&mut terminate) => {
+                    // the sessions of every cinema are replaced in a transaction, so an interrupted run leaves no mess
+                    let summary = report.summary();
+                    warn!(
+                        "{}: the run is interrupted after {} cinemas, {} movies, {} sessions",
+                        date, summary.cinemas.parsed, summary.movies.parsed, summary.sessions.parsed
+                    );
+                    // the interruption is an error of the run, as an unreachable schedule is
+                    db.finish_scrape_run(
+                        run_id,
+                        RunStatus::Failed,
+                        summary.cinemas.parsed as i32,
+                        summary.movies.parsed as i32,
+                        summary.sessions.parsed as i32,
+                        summary.errors() as i32 + 1,
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            }
+        }
+
+        let now = db.clock().now_msk().naive_local();
+        let (next, at) = schedule.next_job(now);
+        info!("next run ({:?}) at {}", next, at);
+
+        tokio::select! {
+            _ = tokio::time::sleep((at - now).to_std().unwrap_or_default()) => {}
+            _ = shutdown(&mut terminate) => {
+                info!("daemon: stopped");
+                return Ok(());
+            }
+        }
+
+        job = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule() -> Schedule {
+        Schedule {
+            every: Duration::minutes(30),
+            nightly_at: NaiveTime::from_hms_opt(3, 0, 0).unwrap(),
+            nightly_days: 2,
+        }
+    }
+
+    fn at(h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 12, 23).unwrap().and_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_next_job() {
+        let schedule = schedule();
+
+        assert_eq!(schedule.next_job(at(14, 10)), (Job::Today, at(14, 30)));
+        // exactly on a tick
+        assert_eq!(schedule.next_job(at(14, 30)), (Job::Today, at(15, 0)));
+        // the nightly job wins the same tick
+        assert_eq!(schedule.next_job(at(2, 45)), (Job::Nightly, at(3, 0)));
+        assert_eq!(schedule.next_job(at(3, 0)), (Job::Today, at(3, 30)));
+        // over midnight
+        assert_eq!(schedule.next_job(at(23, 50)), (Job::Today, at(23, 50) + Duration::minutes(10)));
+    }
+
+    #[test]
+    fn test_next_nightly() {
+        let schedule = schedule();
+
+        assert_eq!(schedule.next_nightly(at(2, 59)), at(3, 0));
+        assert_eq!(schedule.next_nightly(at(3, 0)), at(3, 0) + Duration::days(1));
+    }
+
+    #[test]
+    fn test_dates() {
+        let schedule = schedule();
+        let today = NaiveDate::from_ymd_opt(2023, 12, 31).unwrap();
+
+        assert_eq!(schedule.dates(Job::Today, today), vec![today]);
+        assert_eq!(
+            schedule.dates(Job::Nightly, today),
+            vec![
+                NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 1, 2).unwrap()
+            ]
+        );
+    }
+}
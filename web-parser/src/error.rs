@@ -0,0 +1,57 @@
+use super::Errr;
+use std::{error::Error, fmt};
+
+pub type ScrapResult<T> = Result<T, ScrapError>;
+
+#[derive(Debug)]
+pub enum ScrapError {
+    // an expected element is absent or malformed (the site markup may have changed)
+    Markup(String),
+    // the movie is absent in the "Movies" tab on Moskino
+    NotInCatalogue(String),
+    // a page couldn't be downloaded
+    Fetch { url: String, reason: String },
+    Db(db::Error),
+}
+
+impl ScrapError {
+    pub fn markup(what: impl Into<String>) -> Self {
+        Self::Markup(what.into())
+    }
+
+    pub fn fetch(url: &str, e: Errr) -> Self {
+        Self::Fetch {
+            url: url.to_string(),
+            reason: e.to_string(),
+        }
+    }
+
+    // short name used in the run report
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Markup(_) => "markup",
+            Self::NotInCatalogue(_) => "not_in_catalogue",
+            Self::Fetch { .. } => "fetch",
+            Self::Db(_) => "db",
+        }
+    }
+}
+
+impl fmt::Display for ScrapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Markup(what) => write!(f, "couldn't parse {}", what),
+            Self::NotInCatalogue(title) => write!(f, "'{}': couldn't find a href on moskino website", title),
+            Self::Fetch { url, reason } => write!(f, "'{}': {}", url, reason),
+            Self::Db(e) => write!(f, "db: {}", e),
+        }
+    }
+}
+
+impl Error for ScrapError {}
+
+impl From<db::Error> for ScrapError {
+    fn from(e: db::Error) -> Self {
+        Self::Db(e)
+    }
+}
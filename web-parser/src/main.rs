@@ -4,18 +4,36 @@ extern crate db;
 
 use chrono::NaiveDate;
 use clap::Parser;
-use db::DB;
+use db::{repository::ScheduleRepository, tools::session_start, RunStatus, DB};
 use lazy_static::lazy_static;
 use log::{error, info};
 use scraper::{Html, Selector};
-use std::{env, error::Error, sync::Arc};
+use std::{
+    env,
+    error::Error,
+    fs,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 mod args;
+mod cache;
+mod daemon;
+mod error;
 mod moskino;
+mod report;
 
+use cache::MovieCache;
+use error::{ScrapError, ScrapResult};
+use moskino::catalogue::MoskinoCatalogue;
 use moskino::cinema::MoskinoCinema;
+use moskino::fetcher::{PageFetcher, RecordingFetcher, ReplayFetcher, ReqwestFetcher};
 use moskino::movie::MoskinoMovie;
 use moskino::session::MoskinoSession;
+use moskino::throttle::ThrottledFetcher;
+use report::{Item, Report, Summary};
 
 type Errr = Box<dyn Error + Send + Sync>;
 type Res<T> = Result<T, Errr>;
@@ -32,191 +50,617 @@ lazy_static! {
     static ref SUBITEM_SELECTOR: Selector = Selector::parse(".subitem").unwrap();
 }
 
+// shared by all tasks of a scrape run
+struct Scraper {
+    db: Arc<dyn ScheduleRepository>,
+    fetcher: Arc<dyn PageFetcher>,
+    // movie hrefs are looked up in the catalogue, so it is downloaded only once per run
+    catalogue: MoskinoCatalogue,
+    // movies already stored during the run
+    movies: MovieCache,
+    // enrich movies again even if they are already stored
+    refresh: bool,
+    report: Arc<Report>,
+}
+
 #[tokio::main]
 async fn main() -> Res<()> {
     let args = args::Args::parse();
-    let date = args.day.date();
 
     pretty_env_logger::init();
 
     let db_url = env::var("DATABASE_URL").expect("$DATABASE_URL is not set");
     let db = DB::new(&db_url).await?;
-    let db = Arc::new(db);
     info!("DB: connected");
 
     sqlx::migrate!("../db/migrations").run(&db.conn).await?;
 
+    let db: Arc<dyn ScheduleRepository> = Arc::new(db);
+
+    let fetcher: Arc<dyn PageFetcher> = match (&args.replay, &args.record) {
+        (Some(dir), _) => Arc::new(ReplayFetcher::new(dir)?),
+        (None, Some(dir)) => Arc::new(ThrottledFetcher::new(
+            RecordingFetcher::new(ReqwestFetcher::default(), dir)?,
+            args.throttle(),
+        )),
+        (None, None) => Arc::new(ThrottledFetcher::new(ReqwestFetcher::default(), args.throttle())),
+    };
+
+    if let Some(args::Command::Daemon(daemon_args)) = &args.command {
+        return daemon::run(db, fetcher, daemon_args.schedule(), args.refresh).await;
+    }
+
+    let dates = args.dates(db.clock())?;
+    let mut summaries = vec![];
+    let mut failed = vec![];
+
+    for &date in dates.iter() {
+        let run_id = db.insert_scrape_run(date).await?;
+
+        // a failed date doesn't stop the others (it is recorded into moskino.scrape_runs)
+        match scrape(&db, &fetcher, run_id, date, args.refresh).await {
+            Ok(summary) => summaries.push(summary),
+            Err(e) => {
+                error!("{}: {}", date, e);
+                failed.push(date.to_string());
+                summaries.push(Summary::failed(date, e.to_string()));
+            }
+        }
+    }
+
+    if let Some(path) = args.report {
+        // a list of the summaries of the dates in any case, a failed date has the error
+        fs::write(&path, serde_json::to_string_pretty(&summaries)?)?;
+        info!("report: {}", path.display());
+    }
+
+    if !failed.is_empty() {
+        return Err(format!("couldn't scrape {}", failed.join(", ")).into());
+    }
+
+    Ok(())
+}
+
+fn schedule_url(date: NaiveDate) -> String {
+    format!("{}?date={}", URL_MOSKINO_SCHEDULE, date.format("%Y-%m-%d"))
+}
+
+// scrape the schedule of the date, the outcome is recorded into the run `run_id` of moskino.scrape_runs
+async fn scrape(
+    db: &Arc<dyn ScheduleRepository>,
+    fetcher: &Arc<dyn PageFetcher>,
+    run_id: i32,
+    date: NaiveDate,
+    refresh: bool,
+) -> Res<Summary> {
+    scrape_into(db, fetcher, run_id, date, refresh, Arc::new(Report::new(date))).await
+}
+
+// the same, the items are counted into `report` while they are scraped,
+// so an interrupted run still tells how far it got
+async fn scrape_into(
+    db: &Arc<dyn ScheduleRepository>,
+    fetcher: &Arc<dyn PageFetcher>,
+    run_id: i32,
+    date: NaiveDate,
+    refresh: bool,
+    report: Arc<Report>,
+) -> Res<Summary> {
     info!("Trying to parse by date {}", date);
 
-    let url = args.day.url_by_day();
-    let document = moskino::response(&url).await?;
+    let pages = async {
+        let document = moskino::fetcher::response(fetcher.as_ref(), &schedule_url(date)).await?;
+        let catalogue = MoskinoCatalogue::load(fetcher.as_ref()).await?;
+        Ok::<_, Errr>((document, catalogue))
+    };
 
-    // collect cinema html blocks
-    let content = document
+    // nothing can be scraped without the schedule and the catalogue
+    let (document, catalogue) = match pages.await {
+        Ok(pages) => pages,
+        Err(e) => {
+            db.finish_scrape_run(run_id, RunStatus::Failed, 0, 0, 0, 1).await?;
+            return Err(e);
+        }
+    };
+
+    let scraper = Arc::new(Scraper {
+        db: Arc::clone(db),
+        fetcher: Arc::clone(fetcher),
+        catalogue,
+        movies: MovieCache::default(),
+        refresh,
+        report,
+    });
+
+    let (cinema_ids, complete) = scrap_cinemas(Arc::clone(&scraper), date, cinema_nodes(&document)).await;
+    remove_unseen_cinemas(&scraper, date, &cinema_ids, complete).await;
+
+    info!("done: {} distinct movies", scraper.movies.len());
+
+    let summary = scraper.report.summary();
+    println!("{}", summary);
+    let status = if summary.errors() > 0 {
+        warn!("{} items were skipped", summary.errors());
+        RunStatus::Partial
+    } else {
+        RunStatus::Ok
+    };
+
+    db.finish_scrape_run(
+        run_id,
+        status,
+        summary.cinemas.parsed as i32,
+        scraper.movies.len() as i32,
+        summary.sessions.parsed as i32,
+        summary.errors() as i32,
+    )
+    .await?;
+
+    Ok(summary)
+}
+
+// collect cinema html blocks
+fn cinema_nodes(document: &Html) -> Vec<String> {
+    document
         .root_element()
         .select(&STEP_SELECTOR)
         .map(|node| node.inner_html())
-        .collect::<Vec<String>>();
+        .collect::<Vec<String>>()
+}
 
-    scrap_cinemas(db, date, content).await;
+// collect movie html blocks of a cinema
+fn movie_nodes(cinema_node: &str) -> Vec<String> {
+    Html::parse_document(cinema_node)
+        .root_element()
+        .select(&SCHEDULE_SELECTOR)
+        .map(|node| node.inner_html())
+        .collect::<Vec<String>>()
+}
 
-    info!("done");
-    Ok(())
+// collect session html blocks of a movie
+// (with the .subitem link itself, it holds the session id)
+fn session_nodes(movie_node: &str) -> Vec<String> {
+    Html::parse_document(movie_node)
+        .root_element()
+        .select(&SUBITEM_SELECTOR)
+        .map(|node| node.html())
+        .collect::<Vec<String>>()
 }
 
-async fn scrap_cinemas(db: Arc<DB>, date: NaiveDate, content: Vec<String>) {
+// ids of the cinemas found on the schedule,
+// `complete` is false if some of the cinemas couldn't be parsed or stored
+async fn scrap_cinemas(scraper: Arc<Scraper>, date: NaiveDate, content: Vec<String>) -> (Vec<i32>, bool) {
     let mut handlers = vec![];
 
     for cinema_node in content {
-        let db = Arc::clone(&db);
+        let scraper = Arc::clone(&scraper);
 
         handlers.push(tokio::spawn(async move {
-            let db2 = Arc::clone(&db);
-
-            // TODO handle result
-            let cinema_id = parse_cinema(db, &cinema_node).await.unwrap();
+            // sessions can't be stored without the cinema
+            let Ok(cinema_id) = parse_cinema(&scraper, &cinema_node).await else {
+                return None;
+            };
 
             // collect movie html blocks for every cinema
-            let content = Html::parse_document(&cinema_node)
-                .root_element()
-                .select(&SCHEDULE_SELECTOR)
-                .map(|node| node.inner_html())
-                .collect::<Vec<String>>();
+            let content = movie_nodes(&cinema_node);
+
+            let (sessions, complete) = scrap_movies(Arc::clone(&scraper), date, content).await;
+            store_sessions(&scraper, cinema_id, date, sessions, complete).await;
 
-            scrap_movies(db2, cinema_id, date, content).await;
+            Some(cinema_id)
         }));
     }
 
+    let mut cinema_ids = vec![];
+    let mut complete = true;
+
     for task in handlers {
-        task.await.unwrap();
+        match task.await.unwrap() {
+            Some(cinema_id) => cinema_ids.push(cinema_id),
+            None => complete = false,
+        }
+    }
+
+    (cinema_ids, complete)
+}
+
+// a cinema absent on the schedule has no sessions that day anymore,
+// but only a complete schedule tells that (an empty one is rather a broken page)
+async fn remove_unseen_cinemas(scraper: &Scraper, date: NaiveDate, cinema_ids: &[i32], complete: bool) {
+    if !complete || cinema_ids.is_empty() {
+        warn!("the list of cinemas is incomplete, sessions of the absent cinemas are kept");
+        return;
+    }
+
+    match scraper.db.remove_sessions_of_unseen_cinemas(date, cinema_ids).await {
+        Ok(removed) => {
+            info!("{} sessions of the absent cinemas removed", removed);
+            scraper.report.removed(Item::Session, removed);
+        }
+        Err(e) => {
+            error!("{}", e);
+            scraper.report.failed(
+                Item::Session,
+                &format!("sessions of the cinemas except {:?}", cinema_ids),
+                &e.into(),
+            );
+        }
     }
 }
 
-async fn scrap_movies(db: Arc<DB>, cinema_id: i32, date: NaiveDate, content: Vec<String>) {
+// sessions of a cinema found on the schedule (with ids of their movies),
+// `complete` is false if some of the movies or sessions couldn't be parsed
+async fn scrap_movies(scraper: Arc<Scraper>, date: NaiveDate, content: Vec<String>) -> (Vec<(i32, db::Session)>, bool) {
     let mut handlers = vec![];
 
     for movie_node in content {
-        let db = Arc::clone(&db);
+        let scraper = Arc::clone(&scraper);
 
         handlers.push(tokio::task::spawn(async move {
-            let db2 = Arc::clone(&db);
-
-            // TODO handle result
-            let movie_id = parse_movie(db, &movie_node).await.unwrap();
+            // sessions can't be stored without the movie
+            let Ok(movie_id) = parse_movie(&scraper, &movie_node).await else {
+                return (vec![], false);
+            };
 
             // collect session html blocks for every movie
-            let content = Html::parse_document(&movie_node)
-                .root_element()
-                .select(&SUBITEM_SELECTOR)
-                .map(|node| node.inner_html())
-                .collect::<Vec<String>>();
+            let content = session_nodes(&movie_node);
 
-            scrap_session(db2, cinema_id, movie_id, date, content).await;
+            parse_sessions(&scraper, movie_id, date, content)
         }));
     }
 
+    let mut sessions = vec![];
+    let mut complete = true;
+
     for task in handlers {
-        task.await.unwrap();
+        let (movie_sessions, movie_complete) = task.await.unwrap();
+        sessions.extend(movie_sessions);
+        complete &= movie_complete;
     }
-}
 
-async fn scrap_session(db: Arc<DB>, cinema_id: i32, movie_id: i32, date: NaiveDate, content: Vec<String>) {
-    let mut handlers = vec![];
-
-    for session in content {
-        let db = Arc::clone(&db);
+    (sessions, complete)
+}
 
-        handlers.push(tokio::task::spawn(async move {
-            parse_session(db, cinema_id, movie_id, date, &session).await;
-        }));
+fn parse_sessions(scraper: &Scraper, movie_id: i32, date: NaiveDate, content: Vec<String>) -> (Vec<(i32, db::Session)>, bool) {
+    let mut sessions = vec![];
+    let mut complete = true;
+
+    for session_node in content {
+        match MoskinoSession::from_node(&session_node) {
+            Ok(session) => {
+                scraper.report.parsed(Item::Session);
+
+                // temp wrap
+                let session = db::Session {
+                    moskino_session_id: session.id,
+                    cinema_name: "".into(),
+                    starts_at: session_start(date, session.time),
+                    showdate: date,
+                    price: session.price,
+                    price_kind: session.price_kind,
+                    badges: session.badges,
+                };
+
+                sessions.push((movie_id, session));
+            }
+            Err(e) => {
+                error!("{}", e);
+                scraper.report.failed(Item::Session, &format!("movie_id: {}", movie_id), &e);
+                complete = false;
+            }
+        }
     }
 
-    for task in handlers {
-        task.await.unwrap();
-    }
+    (sessions, complete)
 }
 
-async fn parse_session(db: Arc<DB>, cinema_id: i32, movie_id: i32, date: NaiveDate, session_node: &str) {
-    match MoskinoSession::from_node(session_node) {
-        Ok(session) => {
-            // temp wrap
-            let session = db::Session {
-                cinema_name: "".into(),
-                showtime: session.time,
-                showdate: date,
-                price: session.price,
-            };
-
-            let res = db.insert_session(&session, cinema_id, movie_id).await;
+// the day's schedule of the cinema is replaced at once, so cancelled sessions disappear,
+// but only if it was scraped completely, otherwise the sessions are just added
+async fn store_sessions(scraper: &Scraper, cinema_id: i32, date: NaiveDate, sessions: Vec<(i32, db::Session)>, complete: bool) {
+    if !complete {
+        warn!("cinema_id: {}: the schedule is incomplete, old sessions are kept", cinema_id);
+    }
 
-            match res {
-                Ok(_) => {
-                    info!(
-                        "inserted session '{} - {}' for movie: {} and cinema: {}",
-                        session.showtime, session.price, movie_id, cinema_id
-                    );
-                }
-                Err(e) => match e {
-                    sqlx::Error::Database(e) if e.constraint() == Some("sessions_cinema_id_movie_id_showdate_showtime_price_key") => {
-                        warn!(
-                            "DUPLICATE: '{} - {}' for movie_id: {}, cinema_id: {}",
-                            session.showtime, session.price, movie_id, cinema_id
-                        );
-                    }
-                    _ => {
-                        error!("{}", e);
-                    }
-                },
-            }
+    match scraper.db.replace_sessions(cinema_id, date, &sessions, complete).await {
+        Ok(replaced) => {
+            info!(
+                "cinema_id: {}: {} sessions inserted, {} already stored, {} removed",
+                cinema_id, replaced.inserted, replaced.existing, replaced.removed
+            );
+            scraper.report.sessions_replaced(&replaced);
         }
         Err(e) => {
             error!("{}", e);
+            scraper.report.failed(
+                Item::Session,
+                &format!("cinema_id: {}, {} sessions", cinema_id, sessions.len()),
+                &e.into(),
+            );
         }
     }
 }
 
-async fn parse_cinema(db: Arc<DB>, cinema_node: &str) -> Res<i32> {
+async fn parse_cinema(scraper: &Scraper, cinema_node: &str) -> ScrapResult<i32> {
     match MoskinoCinema::from_node(cinema_node) {
         Ok(cinema) => {
             info!("{}", cinema.name);
+            scraper.report.parsed(Item::Cinema);
 
             // temp wrap
-            let cinema = db::Cinema { id: 0, name: cinema.name };
+            let cinema = db::Cinema {
+                id: 0,
+                name: cinema.name,
+                address: cinema.address,
+                metro: cinema.metro,
+                metro_color: cinema.metro_color,
+            };
 
-            Ok(db.insert_cinema(&cinema).await?)
+            match scraper.db.insert_cinema(&cinema).await {
+                Ok(cinema_id) => {
+                    scraper.report.inserted(Item::Cinema);
+                    Ok(cinema_id)
+                }
+                Err(e) => {
+                    let e = ScrapError::from(e);
+                    error!("{}", e);
+                    scraper.report.failed(Item::Cinema, &cinema.name, &e);
+                    Err(e)
+                }
+            }
         }
         Err(e) => {
             error!("{}", e);
+            scraper.report.failed(Item::Cinema, "", &e);
             Err(e)
         }
     }
 }
 
-async fn parse_movie(db: Arc<DB>, movie_node: &str) -> Res<i32> {
-    match MoskinoMovie::from_node(movie_node) {
-        Ok(movie) => {
-            info!("{}", movie.title);
+// every distinct movie of the run is enriched and stored only once
+async fn parse_movie(scraper: &Scraper, movie_node: &str) -> ScrapResult<i32> {
+    let draft = match MoskinoMovie::draft_from_node(movie_node) {
+        Ok(draft) => draft,
+        Err(e) => {
+            error!("{}", e);
+            scraper.report.failed(Item::Movie, "", &e);
+            return Err(e);
+        }
+    };
+
+    scraper.report.parsed(Item::Movie);
+
+    let key = (draft.title.clone(), draft.year);
+    let stored_now = AtomicBool::new(false);
+
+    let movie_id = scraper
+        .movies
+        .get_or_try_init(key, || {
+            stored_now.store(true, Ordering::Relaxed);
+            store_movie(scraper, draft)
+        })
+        .await?;
+
+    // the movie was stored by another task of the run
+    if !stored_now.load(Ordering::Relaxed) {
+        scraper.report.duplicated(Item::Movie);
+    }
+
+    Ok(movie_id)
+}
+
+async fn store_movie(scraper: &Scraper, mut movie: MoskinoMovie) -> ScrapResult<i32> {
+    let title = movie.title.clone();
 
-            // temp wrap
-            let movie = db::Movie {
-                title: movie.title,
-                year: movie.year,
-                genre: movie.genre,
-                director: movie.director,
-                description: movie.description,
-                href_moskino: movie.href_moskino,
-                href_kinopoisk: movie.href_kinopoisk,
-                country: movie.country,
-                duration: movie.duration,
-                age: movie.age,
-                tagline: movie.tagline,
-            };
+    let stored = async {
+        if !scraper.refresh {
+            if let Some(movie_id) = scraper.db.q_get_enriched_movie_id(&movie.title, movie.year).await? {
+                info!("{}: already stored", movie.title);
+                scraper.report.duplicated(Item::Movie);
+                return Ok(movie_id);
+            }
+        }
 
-            Ok(db.insert_movie(&movie).await?)
+        // the movie is stored even if some details are missing
+        let problems = movie.enrich(scraper.fetcher.as_ref(), &scraper.catalogue).await;
+        info!("{}", movie.title);
+
+        // temp wrap
+        let movie = db::Movie {
+            id: 0,
+            title: movie.title,
+            year: movie.year,
+            genre: movie.genre,
+            director: movie.director,
+            description: movie.description,
+            href_moskino: movie.href_moskino,
+            href_kinopoisk: movie.href_kinopoisk,
+            country: movie.country,
+            duration: movie.duration,
+            age: movie.age,
+            tagline: movie.tagline,
+        };
+
+        let movie_id = scraper.db.insert_movie(&movie).await?;
+
+        // a movie absent on a site isn't looked up again, unless the site couldn't be reached
+        if !problems.iter().any(|e| matches!(e, ScrapError::Fetch { .. })) {
+            scraper.db.set_movie_enriched(movie_id).await?;
         }
+
+        scraper.report.inserted(Item::Movie);
+        scraper.report.partial(Item::Movie, &movie.title, &problems);
+
+        Ok(movie_id)
+    };
+
+    match stored.await {
+        Ok(movie_id) => Ok(movie_id),
         Err(e) => {
+            let e = ScrapError::Db(e);
             error!("{}", e);
+            scraper.report.failed(Item::Movie, &title, &e);
             Err(e)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use db::{clock::FixedClock, memory::MemoryRepository, Badge, Keyset, MovieSort};
+    use moskino::fetcher::test_fetcher;
+
+    // walks the saved schedule page the same way the scraper does
+    #[tokio::test]
+    async fn test_scrap_fixtures() {
+        let fetcher = test_fetcher();
+        let document = moskino::fetcher::response(&fetcher, "https://mos-kino.ru/schedule/?date=2023-12-23")
+            .await
+            .unwrap();
+
+        let catalogue = MoskinoCatalogue::load(&fetcher).await.unwrap();
+
+        let cinemas = cinema_nodes(&document);
+        assert_eq!(cinemas.len(), 2);
+
+        let names = cinemas
+            .iter()
+            .map(|node| MoskinoCinema::from_node(node).unwrap().name)
+            .collect::<Vec<String>>();
+        assert_eq!(names, vec!["Сатурн", "Березка"]);
+
+        let movies = movie_nodes(&cinemas[0]);
+        assert_eq!(movies.len(), 3);
+
+        let mut movie = MoskinoMovie::draft_from_node(&movies[1]).unwrap();
+        assert!(movie.enrich(&fetcher, &catalogue).await.is_empty());
+        assert_eq!(movie.title, "Бешеные псы");
+        assert_eq!(movie.year, Some(1991));
+        assert_eq!(movie.href_moskino.as_deref(), Some("https://mos-kino.ru/film/beshenye-psy/"));
+        assert_eq!(movie.href_kinopoisk.as_deref(), Some("https://www.kinopoisk.ru//film/394/sr/1/"));
+
+        let sessions = session_nodes(&movies[1])
+            .iter()
+            .map(|node| MoskinoSession::from_node(node).unwrap())
+            .map(|session| (session.id, session.time.format("%H:%M").to_string(), session.price, session.badges))
+            .collect::<Vec<(i64, String, i32, Vec<Badge>)>>();
+        assert_eq!(
+            sessions,
+            vec![
+                (96619400, "21:30".to_string(), 250, vec![Badge::TwoD, Badge::Subtitles]),
+                (96619401, "00:30".to_string(), 300, vec![Badge::TwoD, Badge::Original])
+            ]
+        );
+
+        // a movie missing from the moskino catalogue
+        let movies = movie_nodes(&cinemas[1]);
+        assert_eq!(movies.len(), 3);
+        let mut movie = MoskinoMovie::draft_from_node(&movies[2]).unwrap();
+        let problems = movie.enrich(&fetcher, &catalogue).await;
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].kind(), "not_in_catalogue");
+        // what is known is kept
+        assert_eq!(movie.year, Some(2023));
+        assert_eq!(movie.href_moskino, None);
+        assert_eq!(
+            movie.href_kinopoisk.as_deref(),
+            Some("https://www.kinopoisk.ru//film/5047485/sr/1/")
+        );
+
+        let total_sessions: usize = cinemas
+            .iter()
+            .flat_map(|cinema| movie_nodes(cinema))
+            .map(|movie| session_nodes(&movie).len())
+            .sum();
+        assert_eq!(total_sessions, 9);
+    }
+
+    // the whole run over the saved pages, stored without postgres
+    #[tokio::test]
+    async fn test_scrape_into_memory() {
+        let date = NaiveDate::from_ymd_opt(2023, 12, 23).unwrap();
+        // 23:00 in Moscow
+        let clock = FixedClock(Utc.with_ymd_and_hms(2023, 12, 23, 20, 0, 0).unwrap());
+        let db: Arc<dyn ScheduleRepository> = Arc::new(MemoryRepository::new(Arc::new(clock)));
+        let fetcher: Arc<dyn PageFetcher> = Arc::new(test_fetcher());
+
+        let run_id = db.insert_scrape_run(date).await.unwrap();
+        let summary = scrape(&db, &fetcher, run_id, date, false).await.unwrap();
+        assert_eq!(summary.sessions.parsed, 9);
+
+        let run = db.q_get_latest_run_by_date(date).await.unwrap().unwrap();
+        assert_eq!((run.id, run.status, run.sessions), (run_id, RunStatus::Ok, 9));
+
+        // the whole day
+        assert_eq!(db.q_count_movies(date, clock.0 - chrono::Duration::days(1), None).await.unwrap(), 5);
+
+        // only the session after midnight is still ahead
+        let movies = db
+            .q_get_movies_short(date, clock.0, None, MovieSort::Title, &Keyset::First, 10)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(movies.len(), 1);
+        assert_eq!(movies[0].title, "Бешеные псы");
+
+        let sessions = db.q_get_sessions_all(movies[0].id, date, clock.0, None).await.unwrap().unwrap();
+        assert_eq!(sessions[0].moskino_session_id, 96619401);
+        assert_eq!(sessions[0].cinema_name, "Сатурн");
+
+        // the next run doesn't look the movies up again, even the one missing from the moskino catalogue
+        let run_id = db.insert_scrape_run(date).await.unwrap();
+        let summary = scrape(&db, &fetcher, run_id, date, false).await.unwrap();
+        assert_eq!((summary.movies.inserted, summary.movies.partial), (0, 0));
+
+        // a cinema that is not on the schedule anymore loses its sessions of the date
+        let udarnik = db
+            .insert_cinema(&db::Cinema {
+                name: "Ударник".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let session = db::Session {
+            moskino_session_id: 1,
+            cinema_name: "Ударник".to_string(),
+            starts_at: session_start(date, "23:30".parse().unwrap()),
+            showdate: date,
+            price: 300,
+            price_kind: db::PriceKind::Paid,
+            badges: vec![],
+        };
+        db.replace_sessions(udarnik, date, &[(movies[0].id, session)], true).await.unwrap();
+        assert_eq!(db.q_count_movies_by_cinema(date, clock.0, udarnik, None).await.unwrap(), 1);
+
+        let run_id = db.insert_scrape_run(date).await.unwrap();
+        let summary = scrape(&db, &fetcher, run_id, date, false).await.unwrap();
+        assert_eq!(summary.sessions.removed, 1);
+        assert_eq!(db.q_count_movies_by_cinema(date, clock.0, udarnik, None).await.unwrap(), 0);
+    }
+
+    // the schedule has no year of the movie, it is stored with the one of the movie page and still found by the next run
+    #[tokio::test]
+    async fn test_scrape_movie_without_year() {
+        let date = NaiveDate::from_ymd_opt(2023, 12, 24).unwrap();
+        let clock = FixedClock(Utc.with_ymd_and_hms(2023, 12, 24, 9, 0, 0).unwrap());
+        let db: Arc<dyn ScheduleRepository> = Arc::new(MemoryRepository::new(Arc::new(clock)));
+        let fetcher: Arc<dyn PageFetcher> = Arc::new(test_fetcher());
+
+        let run_id = db.insert_scrape_run(date).await.unwrap();
+        let summary = scrape(&db, &fetcher, run_id, date, false).await.unwrap();
+        assert_eq!((summary.movies.inserted, summary.movies.partial), (1, 0));
+
+        let movies = db
+            .q_get_movies_short(date, clock.0, None, MovieSort::Title, &Keyset::First, 10)
+            .await
+            .unwrap()
+            .unwrap();
+        let movie = db.q_get_movie_by_id(movies[0].id).await.unwrap().unwrap();
+        assert_eq!((movie.title.as_str(), movie.year), ("Олдбой", Some(2003)));
+
+        let run_id = db.insert_scrape_run(date).await.unwrap();
+        let summary = scrape(&db, &fetcher, run_id, date, false).await.unwrap();
+        assert_eq!((summary.movies.inserted, summary.movies.duplicated), (0, 1));
+    }
+}
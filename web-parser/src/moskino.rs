@@ -3,12 +3,19 @@ use regex::Regex;
 use scraper::{Element, ElementRef, Html, Selector};
 use std::{fmt, io};
 
+use super::error::{ScrapError, ScrapResult};
 use super::lazy_static;
 use super::Res;
 
+pub mod catalogue;
 pub mod cinema;
+pub mod fetcher;
 pub mod movie;
 pub mod session;
+pub mod throttle;
+
+use catalogue::MoskinoCatalogue;
+use fetcher::{response, PageFetcher};
 
 pub(super) fn parse_text(node: &ElementRef, selector: &Selector) -> Option<String> {
     if let Some(result) = node.select(selector).next() {
@@ -36,20 +43,6 @@ where
     None
 }
 
-pub(super) async fn response(url: &str) -> Res<Html> {
-    let response = reqwest::get(url).await?;
-    let html_content = response.text().await?;
-
-    Ok(scraper::Html::parse_document(&html_content))
-}
-
-pub(super) fn response_blocking(url: &str) -> Res<Html> {
-    let response = reqwest::blocking::get(url)?;
-    let html_content = response.text()?;
-
-    Ok(scraper::Html::parse_document(&html_content))
-}
-
 // <div class="step" data-id="1">
 //     <div class="aside">
 //         <div class="place-name">Сатурн</div>
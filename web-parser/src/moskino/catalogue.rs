@@ -0,0 +1,97 @@
+use super::*;
+use std::collections::{HashMap, HashSet};
+
+pub(super) static URL_MOSKINO_MOVIES: &str = "https://mos-kino.ru/film/";
+pub(super) static URL_MOSKINO: &str = "https://mos-kino.ru";
+
+// protection against a paging loop on the site
+static MAX_PAGES: usize = 50;
+
+lazy_static! {
+    static ref MOSKINO_MOVIES: Selector = Selector::parse(".item.toh_paging_item a.movie-item .title").unwrap();
+    static ref MOSKINO_MOVIES_MORE: Selector = Selector::parse(".toh_paging a.toh_paging_more").unwrap();
+}
+
+// "Movies" tab on Moskino: movie title -> href to the movie page
+//
+// <div class="item toh_paging_item">
+//     <a href="/film/oldboy/" class="movie-item">
+//         <div class="title">Олдбой</div>
+//     </a>
+// </div>
+// ...
+// <div class="toh_paging">
+//     <a href="/film/?PAGEN_1=2" class="toh_paging_more">Показать ещё</a>
+// </div>
+#[derive(Debug, Default)]
+pub struct MoskinoCatalogue {
+    hrefs: HashMap<String, String>,
+}
+
+impl MoskinoCatalogue {
+    // downloads every page of the catalogue
+    pub async fn load(fetcher: &dyn PageFetcher) -> Res<Self> {
+        let mut catalogue = Self::default();
+        let mut visited = HashSet::new();
+        let mut url = Some(URL_MOSKINO_MOVIES.to_string());
+
+        while let Some(current) = url.take() {
+            if !visited.insert(current.clone()) || visited.len() > MAX_PAGES {
+                warn!("catalogue: stop paging at '{}'", current);
+                break;
+            }
+
+            let html_content = fetcher.fetch(&current).await?;
+            url = catalogue.parse_page(&html_content);
+        }
+
+        info!("catalogue: {} movies on {} page(s)", catalogue.len(), visited.len());
+
+        Ok(catalogue)
+    }
+
+    // collects movies of the page, returns url of the next page
+    fn parse_page(&mut self, html_content: &str) -> Option<String> {
+        let html = Html::parse_document(html_content);
+
+        // 1: select all elements based on a given selector
+        // 2: transform elements into pairs (element text, parent element "href" attribute)
+        for (title, href) in html.select(&MOSKINO_MOVIES).filter_map(|el| {
+            let title = el.text().next()?.trim();
+            let href = el.parent_element()?.attr("href")?;
+            Some((title, href))
+        }) {
+            self.hrefs.entry(title.to_string()).or_insert_with(|| URL_MOSKINO.to_owned() + href);
+        }
+
+        html.select(&MOSKINO_MOVIES_MORE)
+            .next()
+            .and_then(|a| a.attr("href"))
+            .map(|href| URL_MOSKINO.to_owned() + href)
+    }
+
+    pub fn href(&self, title: &str) -> Option<&str> {
+        self.hrefs.get(title).map(|href| href.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.hrefs.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fetcher::test_fetcher;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_load_all_pages() {
+        let catalogue = MoskinoCatalogue::load(&test_fetcher()).await.unwrap();
+
+        assert_eq!(catalogue.len(), 4);
+        assert_eq!(catalogue.href("Бешеные псы"), Some("https://mos-kino.ru/film/beshenye-psy/"));
+        // second page
+        assert_eq!(catalogue.href("Олдбой"), Some("https://mos-kino.ru/film/oldboy/"));
+        assert_eq!(catalogue.href("Ёлки 10"), None);
+    }
+}
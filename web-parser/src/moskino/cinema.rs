@@ -2,23 +2,91 @@ use super::*;
 
 lazy_static! {
     static ref PLACE_SELECTOR: Selector = Selector::parse(".place-name").unwrap();
+    static ref ADDRESS_SELECTOR: Selector = Selector::parse(".contact p").unwrap();
+    static ref METRO_SELECTOR: Selector = Selector::parse(".metro").unwrap();
+    static ref METRO_LINE_SELECTOR: Selector = Selector::parse(".metro span").unwrap();
+    static ref R_COLOR: Regex = Regex::new(r"color:\s*(#[0-9A-Fa-f]{6}|#[0-9A-Fa-f]{3})\b").unwrap();
 }
 
 #[derive(Default, Debug)]
 pub struct MoskinoCinema {
     pub name: String,
+    pub address: Option<String>,
+    pub metro: Option<String>,
+    // colour of the metro line, e.g. #EF8532
+    pub metro_color: Option<String>,
 }
 
 impl MoskinoCinema {
-    pub fn from_node(node: &str) -> Res<MoskinoCinema> {
+    // .place-name
+    // .contact p
+    // .metro (the station) and .metro span (the line marker)
+    pub fn from_node(node: &str) -> ScrapResult<MoskinoCinema> {
         let html = Html::parse_document(node);
+        let root = html.root_element();
 
-        match parse_text(&html.root_element(), &PLACE_SELECTOR) {
-            Some(text) => Ok(MoskinoCinema { name: text }),
-            None => {
-                let emsg = "Couldn't parse cinema".to_string();
-                Err(Box::new(io::Error::new(io::ErrorKind::Other, emsg)))
-            }
+        match parse_text(&root, &PLACE_SELECTOR) {
+            Some(name) => Ok(MoskinoCinema {
+                name,
+                address: parse_text(&root, &ADDRESS_SELECTOR),
+                metro: parse_metro(&root),
+                metro_color: parse_metro_color(&root),
+            }),
+            None => Err(ScrapError::markup("cinema name")),
         }
     }
 }
+
+// the station name follows the line marker
+fn parse_metro(node: &ElementRef) -> Option<String> {
+    let metro = node.select(&METRO_SELECTOR).next()?;
+
+    let text = metro
+        .children()
+        .filter_map(|child| child.value().as_text())
+        .map(|text| text.trim())
+        .collect::<String>();
+
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+fn parse_metro_color(node: &ElementRef) -> Option<String> {
+    let style = node.select(&METRO_LINE_SELECTOR).next()?.value().attr("style")?;
+
+    R_COLOR
+        .captures(style)
+        .and_then(|captures| captures.get(1))
+        .map(|color| color.as_str().to_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_node() {
+        let node = r#"
+            <div class="place-name">Сатурн</div>
+            <div class="contact">
+                <p>Снежная ул., д. 18</p>
+                <div class="metro">
+                    <span style="color: #ef8532;">●</span>
+                    Свиблово
+                </div>
+            </div>"#;
+
+        let cinema = MoskinoCinema::from_node(node).unwrap();
+        assert_eq!(cinema.name, "Сатурн");
+        assert_eq!(cinema.address.as_deref(), Some("Снежная ул., д. 18"));
+        assert_eq!(cinema.metro.as_deref(), Some("Свиблово"));
+        assert_eq!(cinema.metro_color.as_deref(), Some("#EF8532"));
+
+        // the contacts are optional
+        let cinema = MoskinoCinema::from_node(r#"<div class="place-name">Сатурн</div>"#).unwrap();
+        assert_eq!((cinema.address, cinema.metro, cinema.metro_color), (None, None, None));
+    }
+}
@@ -0,0 +1,246 @@
+use super::*;
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+static MANIFEST: &str = "manifest.tsv";
+
+// where the parsers get html pages from
+#[async_trait]
+pub trait PageFetcher: Send + Sync {
+    async fn fetch(&self, url: &str) -> Res<String>;
+}
+
+pub async fn response(fetcher: &dyn PageFetcher, url: &str) -> Res<Html> {
+    let html_content = fetcher.fetch(url).await?;
+
+    Ok(Html::parse_document(&html_content))
+}
+
+// live mos-kino.ru / kinopoisk.ru
+#[derive(Default)]
+pub struct ReqwestFetcher {
+    client: reqwest::Client,
+}
+
+#[async_trait]
+impl PageFetcher for ReqwestFetcher {
+    async fn fetch(&self, url: &str) -> Res<String> {
+        let response = self.client.get(url).send().await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok())
+                .map(Duration::from_secs);
+
+            return Err(Box::new(StatusError {
+                url: url.to_string(),
+                status: status.as_u16(),
+                retry_after,
+            }));
+        }
+
+        Ok(response.text().await?)
+    }
+}
+
+// the site answered with an unsuccessful http status
+#[derive(Debug)]
+pub struct StatusError {
+    pub url: String,
+    pub status: u16,
+    // Retry-After header (in seconds only)
+    pub retry_after: Option<Duration>,
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}': http status {}", self.url, self.status)
+    }
+}
+
+impl Error for StatusError {}
+
+// pages saved on disk (see web-parser/fixtures), indexed by the url they were downloaded from
+//
+// manifest.tsv:
+// # comment
+// <url>\t<file relative to the manifest directory>
+pub struct ReplayFetcher {
+    dir: PathBuf,
+    pages: HashMap<String, PathBuf>,
+}
+
+impl ReplayFetcher {
+    pub fn new(dir: impl AsRef<Path>) -> Res<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let pages = read_manifest(&dir.join(MANIFEST))?;
+
+        Ok(Self { dir, pages })
+    }
+}
+
+#[async_trait]
+impl PageFetcher for ReplayFetcher {
+    async fn fetch(&self, url: &str) -> Res<String> {
+        match self.pages.get(url) {
+            Some(file) => Ok(fs::read_to_string(self.dir.join(file))?),
+            None => {
+                let emsg = format!("no saved page for '{}'", url);
+                Err(Box::new(io::Error::new(io::ErrorKind::NotFound, emsg)))
+            }
+        }
+    }
+}
+
+// passes requests to another fetcher and saves every received page,
+// so the directory can be replayed later with ReplayFetcher
+pub struct RecordingFetcher<F> {
+    inner: F,
+    dir: PathBuf,
+    pages: Mutex<HashMap<String, PathBuf>>,
+}
+
+impl<F: PageFetcher> RecordingFetcher<F> {
+    // pages already recorded into the directory are kept (and overwritten when requested again)
+    pub fn new(inner: F, dir: impl AsRef<Path>) -> Res<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let manifest = dir.join(MANIFEST);
+        let pages = if manifest.exists() {
+            read_manifest(&manifest)?
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self {
+            inner,
+            dir,
+            pages: Mutex::new(pages),
+        })
+    }
+
+    fn save(&self, url: &str, html_content: &str) -> Res<()> {
+        let mut pages = self.pages.lock().unwrap();
+
+        let file = match pages.get(url) {
+            Some(file) => file.clone(),
+            None => {
+                let host = reqwest::Url::parse(url)?.host_str().unwrap_or("unknown").to_string();
+                let file = PathBuf::from(host).join(format!("{:04}.html", pages.len() + 1));
+
+                pages.insert(url.to_string(), file.clone());
+                write_manifest(&self.dir.join(MANIFEST), &pages)?;
+                file
+            }
+        };
+
+        let path = self.dir.join(file);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, html_content)?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<F: PageFetcher> PageFetcher for RecordingFetcher<F> {
+    async fn fetch(&self, url: &str) -> Res<String> {
+        let html_content = self.inner.fetch(url).await?;
+        self.save(url, &html_content)?;
+
+        Ok(html_content)
+    }
+}
+
+fn read_manifest(path: &Path) -> Res<HashMap<String, PathBuf>> {
+    let manifest = fs::read_to_string(path)?;
+
+    let mut pages = HashMap::new();
+
+    for (n, line) in manifest.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match line.split_once('\t') {
+            Some((url, file)) => {
+                pages.insert(url.trim().to_string(), PathBuf::from(file.trim()));
+            }
+            None => {
+                let emsg = format!("{}:{}: expected '<url>\\t<file>'", MANIFEST, n + 1);
+                return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, emsg)));
+            }
+        }
+    }
+
+    Ok(pages)
+}
+
+fn write_manifest(path: &Path, pages: &HashMap<String, PathBuf>) -> Res<()> {
+    let mut lines = pages
+        .iter()
+        .map(|(url, file)| format!("{}\t{}", url, file.display()))
+        .collect::<Vec<String>>();
+    lines.sort();
+
+    fs::write(path, format!("# recorded by web-parser --record\n{}\n", lines.join("\n")))?;
+
+    Ok(())
+}
+
+// fixtures shipped with the crate
+#[cfg(test)]
+pub(crate) fn test_fetcher() -> ReplayFetcher {
+    ReplayFetcher::new(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures")).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_replay() {
+        let fetcher = test_fetcher();
+
+        fetcher.fetch("https://mos-kino.ru/film/").await.unwrap();
+        assert!(fetcher.fetch("https://mos-kino.ru/film/unknown/").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let dir = std::env::temp_dir().join(format!("web-parser-record-{}", std::process::id()));
+        let urls = ["https://mos-kino.ru/film/", "https://mos-kino.ru/film/oldboy/"];
+
+        let recorder = RecordingFetcher::new(test_fetcher(), &dir).unwrap();
+        let mut recorded = vec![];
+        for url in urls {
+            recorded.push(recorder.fetch(url).await.unwrap());
+        }
+        // repeated request must not create a second copy
+        recorder.fetch(urls[0]).await.unwrap();
+
+        let replay = ReplayFetcher::new(&dir).unwrap();
+        assert_eq!(replay.pages.len(), urls.len());
+        for (url, page) in urls.iter().zip(recorded) {
+            assert_eq!(replay.fetch(url).await.unwrap(), page);
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -3,8 +3,6 @@ use std::hash::{Hash, Hasher};
 
 static URL_KINOPOISK_SEARCH: &str = "https://www.kinopoisk.ru/index.php?kp_query=";
 static URL_KINOPOISK: &str = "https://www.kinopoisk.ru/";
-static URL_MOSKINO_MOVIES: &str = "https://mos-kino.ru/film/";
-static URL_MOSKINO: &str = "https://mos-kino.ru";
 
 lazy_static! {
     static ref R_AGE: Regex = Regex::new(r"(\d+)").unwrap();
@@ -13,7 +11,6 @@ lazy_static! {
     static ref KP_SELECTOR: Selector = Selector::parse(".name").unwrap();
     static ref KP_A_SELECTOR: Selector = Selector::parse("p.name > a").expect("Failed to parse selector for <a>");
     static ref KP_SPAN_SELECTOR: Selector = Selector::parse("p.name > span.year").expect("Failed to parse selector for <span>");
-    static ref MOSKINO_MOVIES: Selector = Selector::parse(".item.toh_paging_item a.movie-item .title").unwrap();
     static ref MOSKINO_MOVIE_GENRE: Selector = Selector::parse(".info-wrapper p").unwrap();
     static ref MOSKINO_MOVIE_INFO: Selector = Selector::parse(".info-wrapper small").unwrap();
     static ref MOSKINO_MOVIE_TAGLINE: Selector = Selector::parse(".description").unwrap();
@@ -65,8 +62,8 @@ impl MoskinoMovie {
         }
     }
 
-    // pub fn from_node(node: ElementRef<'_>, movies: &mut HashSet<Self>) -> Res<()> {
-    pub fn from_node(node: &str) -> Res<MoskinoMovie> {
+    // title and year from the schedule item
+    pub fn draft_from_node(node: &str) -> ScrapResult<MoskinoMovie> {
         let html = Html::parse_document(node);
 
         // извлекаем название фильма
@@ -74,11 +71,6 @@ impl MoskinoMovie {
             Some(title) => {
                 let mut movie = Self::draft(&title);
 
-                // if movies.contains(&movie) {
-                // warn!("--- '{}' already in set", movie.title);
-                // return Ok(());
-                // }
-
                 // parsing year
                 if let Some(raw_info) = parse_text(&html.root_element(), &SMALL_SELECTOR) {
                     match Self::parse_year(&raw_info) {
@@ -91,25 +83,39 @@ impl MoskinoMovie {
                     }
                 }
 
-                // parsing href moskino
-                tokio::task::block_in_place(|| movie.parse_href_moskino())?;
-
-                // parsing other info
-                tokio::task::block_in_place(|| movie.parse_movie_info())?;
-
-                // parsing href kinopoisk
-                movie.href_kinopoisk = tokio::task::block_in_place(|| Self::parse_href_kinopoisk(&movie))?;
-
-                // info!("^^^ '{}'", movie.title);
-                // movies.insert(movie);
-                // Ok(())
                 Ok(movie)
             }
-            None => {
-                let emsg = "Couldn't parse movie".to_string();
-                Err(Box::new(io::Error::new(io::ErrorKind::Other, emsg)))
+            None => Err(ScrapError::markup("movie title")),
+        }
+    }
+
+    // details from the movie page on Moskino and href to Kinopoisk
+    // the movie keeps whatever was found, returns the problems encountered on the way
+    pub async fn enrich(&mut self, fetcher: &dyn PageFetcher, catalogue: &MoskinoCatalogue) -> Vec<ScrapError> {
+        let mut problems = vec![];
+
+        // parsing href moskino
+        match self.parse_href_moskino(catalogue) {
+            // parsing other info
+            Ok(()) => {
+                if let Err(e) = self.parse_movie_info(fetcher).await {
+                    problems.push(e);
+                }
             }
+            Err(e) => problems.push(e),
+        }
+
+        // parsing href kinopoisk
+        match Self::parse_href_kinopoisk(self, fetcher).await {
+            Ok(href) => self.href_kinopoisk = href,
+            Err(e) => problems.push(e),
+        }
+
+        for e in problems.iter() {
+            warn!("{}", e);
         }
+
+        problems
     }
 
     fn parse_year(raw: &str) -> Res<i32> {
@@ -124,40 +130,23 @@ impl MoskinoMovie {
     }
 
     // search href for a specific movie in the "Movies" tab on Moskino
-    fn parse_href_moskino(&mut self) -> Res<()> {
-        let html = response_blocking(URL_MOSKINO_MOVIES)?;
-
-        // 1: select all elements based on a given selector
-        // 2: transform elements into pairs (element, element text)
-        // 3: find the first pair where the element text matches the movie name
-        // 4: extract the element from the found pair
-        // 5: extract the parent element and the "href" attribute from the element
-        match html
-            .select(&MOSKINO_MOVIES)
-            .filter_map(|el| el.text().next().map(|text| (el, text)))
-            .find(|(_, text)| *text == self.title)
-            .map(|(el, _)| el)
-            .and_then(|el| el.parent_element().and_then(|a| a.attr("href")))
-        {
+    fn parse_href_moskino(&mut self, catalogue: &MoskinoCatalogue) -> ScrapResult<()> {
+        match catalogue.href(&self.title) {
             Some(href) => {
-                self.href_moskino = Some(URL_MOSKINO.to_owned() + href);
+                self.href_moskino = Some(href.to_string());
                 Ok(())
             }
-            None => {
-                warn!("{:?}", self);
-                let emsg = format!("'{}': couldn't find a href on moskino website", self.title);
-                Err(Box::new(io::Error::new(io::ErrorKind::NotFound, emsg)))
-            }
+            None => Err(ScrapError::NotInCatalogue(self.title.clone())),
         }
     }
 
-    fn parse_movie_info(&mut self) -> Res<()> {
-        let url = self.href_moskino.as_deref().ok_or_else(|| {
-            let emsg = format!("'{}': no href to the movie for full parsing", self.title);
-            Box::new(io::Error::new(io::ErrorKind::NotFound, emsg))
-        })?;
+    async fn parse_movie_info(&mut self, fetcher: &dyn PageFetcher) -> ScrapResult<()> {
+        let url = self
+            .href_moskino
+            .as_deref()
+            .ok_or_else(|| ScrapError::NotInCatalogue(self.title.clone()))?;
 
-        let html = response_blocking(url)?;
+        let html = response(fetcher, url).await.map_err(|e| ScrapError::fetch(url, e))?;
         let node = html.root_element();
 
         if let Some(genre) = parse_text(&node, &MOSKINO_MOVIE_GENRE) {
@@ -194,7 +183,9 @@ impl MoskinoMovie {
     }
 
     fn parse_info(&mut self, raw: String) {
-        let splitted: Vec<String> = raw.split("/ ").map(|s| s.to_string()).collect();
+        let mut splitted: Vec<String> = raw.split("/ ").map(|s| s.to_string()).collect();
+        // country / year / duration / age, some of them may be missing
+        splitted.resize(4, String::new());
 
         if !splitted[0].trim().is_empty() {
             self.country = Some(splitted[0].trim().to_string());
@@ -202,7 +193,8 @@ impl MoskinoMovie {
             // warn!("Country");
         }
 
-        if !splitted[1].trim().is_empty() {
+        // the year of the schedule is kept, the movie is stored and looked up by it
+        if self.year.is_none() && !splitted[1].trim().is_empty() {
             if let Ok(year) = splitted[1].trim().parse::<i32>() {
                 self.year = Some(year);
             } else {
@@ -234,11 +226,11 @@ impl MoskinoMovie {
         }
     }
 
-    fn parse_href_kinopoisk(movie: &MoskinoMovie) -> Res<Option<String>> {
+    async fn parse_href_kinopoisk(movie: &MoskinoMovie, fetcher: &dyn PageFetcher) -> ScrapResult<Option<String>> {
         // Kinopoisk query link
         let url = Self::create_url_to_search(&movie.title, movie.year);
 
-        let link = MoskinoMovie::kinopoisk_get_link(url, movie)?;
+        let link = MoskinoMovie::kinopoisk_get_link(url, movie, fetcher).await?;
 
         match link {
             Some(link) => Ok(Some(link)),
@@ -249,7 +241,7 @@ impl MoskinoMovie {
                     movie.year = None;
 
                     let url = Self::create_url_to_search(&movie.title, None);
-                    MoskinoMovie::kinopoisk_get_link(url, &movie)
+                    MoskinoMovie::kinopoisk_get_link(url, &movie, fetcher).await
                 } else {
                     // warn!("KP href");
                     Ok(None)
@@ -258,8 +250,8 @@ impl MoskinoMovie {
         }
     }
 
-    fn kinopoisk_get_link(url: String, movie: &MoskinoMovie) -> Res<Option<String>> {
-        let html = response_blocking(&url)?;
+    async fn kinopoisk_get_link(url: String, movie: &MoskinoMovie, fetcher: &dyn PageFetcher) -> ScrapResult<Option<String>> {
+        let html = response(fetcher, &url).await.map_err(|e| ScrapError::fetch(&url, e))?;
 
         let a = html.select(&KP_A_SELECTOR).next();
 
@@ -361,6 +353,7 @@ impl fmt::Debug for MoskinoMovie {
 
 #[cfg(test)]
 mod tests {
+    use super::fetcher::test_fetcher;
     use super::*;
 
     #[test]
@@ -385,11 +378,13 @@ mod tests {
         );
     }
 
-    #[test]
-    fn test_kinopoisk() {
+    #[tokio::test]
+    async fn test_kinopoisk() {
+        let fetcher = test_fetcher();
+
         // ok some
         let movie = MoskinoMovie::draft_with_year("Бешеные псы", 1991);
-        let result = MoskinoMovie::parse_href_kinopoisk(&movie);
+        let result = MoskinoMovie::parse_href_kinopoisk(&movie, &fetcher).await;
 
         match result {
             Ok(link) => {
@@ -402,7 +397,7 @@ mod tests {
 
         // ok none non-existent movie
         let movie = MoskinoMovie::draft_with_year("Non-existent movie blup blip", 1504);
-        let result = MoskinoMovie::parse_href_kinopoisk(&movie);
+        let result = MoskinoMovie::parse_href_kinopoisk(&movie, &fetcher).await;
 
         match result {
             Ok(link) => {
@@ -415,7 +410,7 @@ mod tests {
 
         // ok some with wrong year
         let movie = MoskinoMovie::draft_with_year("Бешеные псы", 2023);
-        let result = MoskinoMovie::parse_href_kinopoisk(&movie);
+        let result = MoskinoMovie::parse_href_kinopoisk(&movie, &fetcher).await;
 
         match result {
             Ok(link) => {
@@ -428,7 +423,7 @@ mod tests {
 
         // ok some without year
         let movie = MoskinoMovie::draft("Олдбой");
-        let result = MoskinoMovie::parse_href_kinopoisk(&movie);
+        let result = MoskinoMovie::parse_href_kinopoisk(&movie, &fetcher).await;
 
         match result {
             Ok(link) => {
@@ -438,6 +433,42 @@ mod tests {
                 panic!("Error: {:?}", err);
             }
         }
+
+        // ok none when the first search result is another movie
+        let movie = MoskinoMovie::draft_with_year("Следующая жертва", 2023);
+        let result = MoskinoMovie::parse_href_kinopoisk(&movie, &fetcher).await;
+
+        assert_eq!(result.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_enrich() {
+        let fetcher = test_fetcher();
+        let catalogue = MoskinoCatalogue::load(&fetcher).await.unwrap();
+        let node = r#"
+            <div class="title">
+                По щучьему велению <small>2023 / 115 мин / Россия / 6+</small>
+            </div>"#;
+
+        let mut movie = MoskinoMovie::draft_from_node(node).unwrap();
+        assert!(movie.enrich(&fetcher, &catalogue).await.is_empty());
+
+        assert_eq!(movie.title, "По щучьему велению");
+        assert_eq!(movie.year, Some(2023));
+        assert_eq!(movie.genre.as_deref(), Some("Сказка, комедия"));
+        assert_eq!(movie.country.as_deref(), Some("Россия"));
+        assert_eq!(movie.duration, Some(115));
+        assert_eq!(movie.age, Some(6));
+        assert_eq!(movie.director.as_deref(), Some("Александр Войтинский"));
+        assert_eq!(movie.tagline.as_deref(), Some("Емеля и волшебная щука"));
+        assert_eq!(
+            movie.href_moskino.as_deref(),
+            Some("https://mos-kino.ru/film/po-shchuchemu-veleniyu/")
+        );
+        assert_eq!(
+            movie.href_kinopoisk.as_deref(),
+            Some("https://www.kinopoisk.ru//film/4540126/sr/1/")
+        );
     }
 
     // #[test]
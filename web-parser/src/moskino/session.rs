@@ -1,25 +1,90 @@
 use super::*;
+use db::{Badge, PriceKind};
 
 lazy_static! {
+    static ref SUBITEM_SELECTOR: Selector = Selector::parse(".subitem").unwrap();
     static ref TIME_SELECTOR: Selector = Selector::parse(".time").unwrap();
     static ref PRICE_SELECTOR: Selector = Selector::parse(".price").unwrap();
+    static ref BADGE_SELECTOR: Selector = Selector::parse(".badge").unwrap();
     static ref R_PRICE: Regex = Regex::new(r"(\d+) \w").unwrap();
+    static ref R_SESSION_ID: Regex = Regex::new(r"richSession\((\d+)\)").unwrap();
 }
 
 #[derive(Default, Debug, Clone)]
 pub struct MoskinoSession {
+    // id of the session on moskino (tickets are sold by it)
+    pub id: i64,
     pub time: NaiveTime,
+    // 0 unless the session is paid
     pub price: i32,
+    pub price_kind: PriceKind,
+    pub badges: Vec<Badge>,
+}
+
+// 250 P, Бесплатно, По приглашению
+fn parse_price(text: &str) -> (i32, PriceKind) {
+    if let Some(price) = parse_num_with_regex(text, &R_PRICE) {
+        return (price, PriceKind::Paid);
+    }
+
+    let text = text.to_lowercase();
+    if text.contains("бесплатно") {
+        (0, PriceKind::Free)
+    } else if text.contains("приглаш") {
+        (0, PriceKind::Invitation)
+    } else {
+        warn!("unknown session price '{}'", text);
+        (0, PriceKind::Unknown)
+    }
+}
+
+// labels of the badges as they are written on the schedule
+fn parse_badge(label: &str) -> Option<Badge> {
+    match label.to_lowercase().as_str() {
+        "2d" => Some(Badge::TwoD),
+        "3d" => Some(Badge::ThreeD),
+        "субтитры" => Some(Badge::Subtitles),
+        "на языке оригинала" => Some(Badge::Original),
+        "пушкинская карта" => Some(Badge::Pushkin),
+        _ => None,
+    }
 }
 
 impl MoskinoSession {
+    // .subitem href="javascript:ticketManager.richSession(<id>)"
     // .time
+    // .badge (any number of them)
     // .price
-    pub fn from_node(node: &str) -> Res<Self> {
+    pub fn from_node(node: &str) -> ScrapResult<Self> {
         let html = Html::parse_document(node);
 
         let mut session = MoskinoSession::default();
 
+        let href = html
+            .root_element()
+            .select(&SUBITEM_SELECTOR)
+            .next()
+            .and_then(|subitem| subitem.value().attr("href"));
+
+        match href.and_then(|href| parse_num_with_regex(href, &R_SESSION_ID)) {
+            Some(id) => {
+                session.id = id;
+            }
+            None => {
+                return Err(ScrapError::markup(format!("session id '{}'", href.unwrap_or_default())));
+            }
+        }
+
+        for badge in html.root_element().select(&BADGE_SELECTOR) {
+            let label = badge.text().collect::<String>();
+            match parse_badge(label.trim()) {
+                Some(badge) if !session.badges.contains(&badge) => session.badges.push(badge),
+                Some(_) => {}
+                // a new badge on the site shouldn't break the session
+                None => warn!("unknown session badge '{}'", label.trim()),
+            }
+        }
+
         let time = parse_text(&html.root_element(), &TIME_SELECTOR);
         let price = parse_text(&html.root_element(), &PRICE_SELECTOR);
 
@@ -29,25 +94,28 @@ impl MoskinoSession {
                     session.time = time;
                 }
                 Err(e) => {
-                    return Err(e.into());
+                    return Err(ScrapError::markup(format!("showtime '{}': {}", time, e)));
                 }
             }
 
-            match parse_num_with_regex(&price, &R_PRICE) {
-                Some(price) => {
-                    session.price = price;
-                }
-                None => {
-                    // for free sessions
-                    error!("Price");
-                    session.price = 0;
-                }
-            }
+            (session.price, session.price_kind) = parse_price(&price);
 
             Ok(session)
         } else {
-            let emsg = "Couldn't parse showtime".to_string();
-            Err(Box::new(io::Error::new(io::ErrorKind::NotFound, emsg)))
+            Err(ScrapError::markup("showtime"))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_price() {
+        assert_eq!(parse_price("250 P"), (250, PriceKind::Paid));
+        assert_eq!(parse_price("Бесплатно"), (0, PriceKind::Free));
+        assert_eq!(parse_price("По приглашению"), (0, PriceKind::Invitation));
+        assert_eq!(parse_price("уточняйте в кассе"), (0, PriceKind::Unknown));
+    }
+}
@@ -0,0 +1,262 @@
+use super::fetcher::StatusError;
+use super::*;
+use crate::Errr;
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{sync::Semaphore, time::Instant};
+
+// how politely the sites are requested
+#[derive(Debug, Clone)]
+pub struct Throttle {
+    // max simultaneous requests to a host
+    pub concurrency: usize,
+    // overrides of `concurrency` by host (a domain also covers its subdomains)
+    pub host_concurrency: Vec<(String, usize)>,
+    // max requests per second to a host (0 - unlimited)
+    pub rps: f64,
+    // how many times a request answered with 429/5xx (or not answered at all) is repeated
+    pub retries: u32,
+    // delay before the first retry, doubled on every next one
+    pub backoff: Duration,
+}
+
+impl Throttle {
+    fn concurrency(&self, host: &str) -> usize {
+        self.host_concurrency
+            .iter()
+            .find(|(domain, _)| host == domain || host.ends_with(&format!(".{}", domain)))
+            .map_or(self.concurrency, |(_, limit)| *limit)
+            .max(1)
+    }
+
+    fn interval(&self) -> Duration {
+        if self.rps > 0.0 {
+            Duration::from_secs_f64(1.0 / self.rps)
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    fn delay(&self, attempt: u32, e: &Errr) -> Duration {
+        let delay = self.backoff.saturating_mul(2u32.saturating_pow(attempt));
+
+        match e.downcast_ref::<StatusError>().and_then(|e| e.retry_after) {
+            Some(retry_after) => delay.max(retry_after),
+            None => delay,
+        }
+    }
+}
+
+struct Host {
+    permits: Semaphore,
+    // the earliest moment the next request may start
+    next_slot: Mutex<Instant>,
+}
+
+impl Host {
+    async fn wait_turn(&self, interval: Duration) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + interval;
+            slot
+        };
+
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+// passes requests to another fetcher keeping the limits of every host
+pub struct ThrottledFetcher<F> {
+    inner: F,
+    throttle: Throttle,
+    hosts: Mutex<HashMap<String, Arc<Host>>>,
+}
+
+impl<F: PageFetcher> ThrottledFetcher<F> {
+    pub fn new(inner: F, throttle: Throttle) -> Self {
+        Self {
+            inner,
+            throttle,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn host(&self, url: &str) -> Res<Arc<Host>> {
+        let name = reqwest::Url::parse(url)?.host_str().unwrap_or_default().to_string();
+        let mut hosts = self.hosts.lock().unwrap();
+
+        let host = hosts.entry(name).or_insert_with_key(|name| {
+            Arc::new(Host {
+                permits: Semaphore::new(self.throttle.concurrency(name)),
+                next_slot: Mutex::new(Instant::now()),
+            })
+        });
+
+        Ok(Arc::clone(host))
+    }
+}
+
+#[async_trait]
+impl<F: PageFetcher> PageFetcher for ThrottledFetcher<F> {
+    async fn fetch(&self, url: &str) -> Res<String> {
+        let host = self.host(url)?;
+        let mut attempt = 0;
+
+        loop {
+            let result = {
+                let _permit = host.permits.acquire().await?;
+                host.wait_turn(self.throttle.interval()).await;
+                self.inner.fetch(url).await
+            };
+
+            match result {
+                Err(e) if attempt < self.throttle.retries && is_retryable(&e) => {
+                    let delay = self.throttle.delay(attempt, &e);
+                    warn!("{}, retry in {:?}", e, delay);
+
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+// 429 Too Many Requests, 5xx or the site is unreachable
+fn is_retryable(e: &Errr) -> bool {
+    if let Some(e) = e.downcast_ref::<StatusError>() {
+        return e.status == 429 || (500..600).contains(&e.status);
+    }
+    if let Some(e) = e.downcast_ref::<reqwest::Error>() {
+        return e.is_timeout() || e.is_connect();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // answers with the given statuses first, then with a page
+    #[derive(Default)]
+    struct FakeFetcher {
+        statuses: Mutex<Vec<u16>>,
+        calls: AtomicUsize,
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl PageFetcher for FakeFetcher {
+        async fn fetch(&self, url: &str) -> Res<String> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            match self.statuses.lock().unwrap().pop() {
+                Some(status) => Err(Box::new(StatusError {
+                    url: url.to_string(),
+                    status,
+                    retry_after: None,
+                })),
+                None => Ok("<html></html>".to_string()),
+            }
+        }
+    }
+
+    fn throttle() -> Throttle {
+        Throttle {
+            concurrency: 2,
+            host_concurrency: vec![("kinopoisk.ru".to_string(), 1)],
+            rps: 0.0,
+            retries: 3,
+            backoff: Duration::from_millis(500),
+        }
+    }
+
+    fn fake(statuses: Vec<u16>) -> FakeFetcher {
+        FakeFetcher {
+            statuses: Mutex::new(statuses),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_with_backoff() {
+        let fetcher = ThrottledFetcher::new(fake(vec![503, 429]), throttle());
+
+        let start = Instant::now();
+        fetcher.fetch("https://mos-kino.ru/film/").await.unwrap();
+
+        assert_eq!(fetcher.inner.calls.load(Ordering::SeqCst), 3);
+        // 3 requests + 500ms + 1000ms of backoff
+        assert_eq!(start.elapsed(), Duration::from_millis(300 + 1500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_give_up() {
+        let fetcher = ThrottledFetcher::new(fake(vec![500, 500, 500, 500, 500]), throttle());
+        assert!(fetcher.fetch("https://mos-kino.ru/film/").await.is_err());
+        assert_eq!(fetcher.inner.calls.load(Ordering::SeqCst), 4);
+
+        // not found is not repeated
+        let fetcher = ThrottledFetcher::new(fake(vec![404]), throttle());
+        assert!(fetcher.fetch("https://mos-kino.ru/film/").await.is_err());
+        assert_eq!(fetcher.inner.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_concurrency_by_host() {
+        let fetcher = Arc::new(ThrottledFetcher::new(fake(vec![]), throttle()));
+
+        for (url, limit) in [("https://mos-kino.ru/film/", 2), ("https://www.kinopoisk.ru/", 1)] {
+            fetcher.inner.max_in_flight.store(0, Ordering::SeqCst);
+
+            let mut handlers = vec![];
+            for _ in 0..6 {
+                let fetcher = Arc::clone(&fetcher);
+                handlers.push(tokio::spawn(async move { fetcher.fetch(url).await.unwrap() }));
+            }
+            for task in handlers {
+                task.await.unwrap();
+            }
+
+            assert_eq!(fetcher.inner.max_in_flight.load(Ordering::SeqCst), limit);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_requests_per_second() {
+        let throttle = Throttle {
+            concurrency: 10,
+            rps: 2.0,
+            ..throttle()
+        };
+        let fetcher = Arc::new(ThrottledFetcher::new(fake(vec![]), throttle));
+
+        let start = Instant::now();
+        let mut handlers = vec![];
+        for _ in 0..5 {
+            let fetcher = Arc::clone(&fetcher);
+            handlers.push(tokio::spawn(
+                async move { fetcher.fetch("https://mos-kino.ru/film/").await.unwrap() },
+            ));
+        }
+        for task in handlers {
+            task.await.unwrap();
+        }
+
+        // the 5th request starts 2 seconds after the 1st one
+        assert_eq!(start.elapsed(), Duration::from_millis(2000 + 100));
+    }
+}
@@ -0,0 +1,221 @@
+use super::error::ScrapError;
+use chrono::NaiveDate;
+use db::SessionsReplaced;
+use serde::Serialize;
+use std::{fmt, sync::Mutex};
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Item {
+    Cinema,
+    Movie,
+    Session,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Counters {
+    // found on the schedule page
+    pub parsed: usize,
+    // stored by the run
+    pub inserted: usize,
+    // already stored (by the run or earlier)
+    pub duplicated: usize,
+    // stored with missing details
+    pub partial: usize,
+    // skipped because of an error
+    pub failed: usize,
+    // stored before, but absent on the site now
+    pub removed: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Problem {
+    pub item: Item,
+    // cinema name, movie title or session node
+    pub subject: String,
+    pub kind: &'static str,
+    pub reason: String,
+    // whether the item was dropped
+    pub skipped: bool,
+}
+
+// outcome of a scrape run
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub date: NaiveDate,
+    pub cinemas: Counters,
+    pub movies: Counters,
+    pub sessions: Counters,
+    pub problems: Vec<Problem>,
+    // why the date couldn't be scraped at all (the counters are empty then)
+    pub error: Option<String>,
+}
+
+// collected by all tasks of a run
+pub struct Report {
+    summary: Mutex<Summary>,
+}
+
+impl Report {
+    pub fn new(date: NaiveDate) -> Self {
+        Self {
+            summary: Mutex::new(Summary {
+                date,
+                cinemas: Counters::default(),
+                movies: Counters::default(),
+                sessions: Counters::default(),
+                problems: vec![],
+                error: None,
+            }),
+        }
+    }
+
+    pub fn parsed(&self, item: Item) {
+        self.update(item, |c| c.parsed += 1);
+    }
+
+    pub fn inserted(&self, item: Item) {
+        self.update(item, |c| c.inserted += 1);
+    }
+
+    pub fn duplicated(&self, item: Item) {
+        self.update(item, |c| c.duplicated += 1);
+    }
+
+    pub fn sessions_replaced(&self, replaced: &SessionsReplaced) {
+        self.update(Item::Session, |c| {
+            c.inserted += replaced.inserted;
+            c.duplicated += replaced.existing;
+            c.removed += replaced.removed;
+        });
+    }
+
+    // stored before, but absent on the site now
+    pub fn removed(&self, item: Item, removed: u64) {
+        self.update(item, |c| c.removed += removed);
+    }
+
+    // the item is stored, but some of its details are missing
+    pub fn partial(&self, item: Item, subject: &str, problems: &[ScrapError]) {
+        if problems.is_empty() {
+            return;
+        }
+
+        self.update(item, |c| c.partial += 1);
+        for e in problems {
+            self.problem(item, subject, e, false);
+        }
+    }
+
+    pub fn failed(&self, item: Item, subject: &str, e: &ScrapError) {
+        self.update(item, |c| c.failed += 1);
+        self.problem(item, subject, e, true);
+    }
+
+    pub fn summary(&self) -> Summary {
+        self.summary.lock().unwrap().clone()
+    }
+
+    fn problem(&self, item: Item, subject: &str, e: &ScrapError, skipped: bool) {
+        self.summary.lock().unwrap().problems.push(Problem {
+            item,
+            subject: subject.to_string(),
+            kind: e.kind(),
+            reason: e.to_string(),
+            skipped,
+        });
+    }
+
+    fn update(&self, item: Item, f: impl FnOnce(&mut Counters)) {
+        let mut summary = self.summary.lock().unwrap();
+
+        match item {
+            Item::Cinema => f(&mut summary.cinemas),
+            Item::Movie => f(&mut summary.movies),
+            Item::Session => f(&mut summary.sessions),
+        }
+    }
+}
+
+impl Summary {
+    pub fn failed(date: NaiveDate, error: String) -> Self {
+        Self {
+            error: Some(error),
+            ..Report::new(date).summary()
+        }
+    }
+
+    pub fn errors(&self) -> usize {
+        self.cinemas.failed + self.movies.failed + self.sessions.failed
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "scrape of {}", self.date)?;
+        if let Some(error) = &self.error {
+            return writeln!(f, "FAILED: {}", error);
+        }
+        writeln!(
+            f,
+            "{:<10}{:>8}{:>10}{:>12}{:>9}{:>8}{:>9}",
+            "", "parsed", "inserted", "duplicated", "partial", "failed", "removed"
+        )?;
+
+        for (name, c) in [("cinemas", &self.cinemas), ("movies", &self.movies), ("sessions", &self.sessions)] {
+            writeln!(
+                f,
+                "{:<10}{:>8}{:>10}{:>12}{:>9}{:>8}{:>9}",
+                name, c.parsed, c.inserted, c.duplicated, c.partial, c.failed, c.removed
+            )?;
+        }
+
+        for p in self.problems.iter() {
+            writeln!(
+                f,
+                "{} {:?} '{}': [{}] {}",
+                if p.skipped { "FAILED " } else { "PARTIAL" },
+                p.item,
+                p.subject,
+                p.kind,
+                p.reason
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let report = Report::new(NaiveDate::from_ymd_opt(2023, 12, 23).unwrap());
+
+        report.parsed(Item::Movie);
+        report.parsed(Item::Movie);
+        report.inserted(Item::Movie);
+        report.duplicated(Item::Movie);
+        report.partial(Item::Movie, "Ёлки 10", &[ScrapError::NotInCatalogue("Ёлки 10".to_string())]);
+        report.failed(Item::Session, "", &ScrapError::markup("showtime"));
+
+        let summary = report.summary();
+        assert_eq!(summary.movies.parsed, 2);
+        assert_eq!(summary.movies.partial, 1);
+        assert_eq!(summary.errors(), 1);
+
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["date"], "2023-12-23");
+        assert_eq!(json["sessions"]["failed"], 1);
+        assert_eq!(json["problems"][0]["item"], "movie");
+        assert_eq!(json["problems"][0]["kind"], "not_in_catalogue");
+        assert_eq!(json["problems"][0]["skipped"], false);
+        assert_eq!(json["error"], serde_json::Value::Null);
+
+        let json = serde_json::to_value(Summary::failed(summary.date, "schedule page: timeout".to_string())).unwrap();
+        assert_eq!(json["error"], "schedule page: timeout");
+        assert_eq!(json["sessions"]["parsed"], 0);
+    }
+}